@@ -1,6 +1,6 @@
 use std::{
     any::Any,
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io::Cursor,
     sync::{
         atomic::{AtomicU64, Ordering},
@@ -16,8 +16,19 @@ pub struct WebBackend {
     pub stderr: Mutex<String>,
     pub trace: Mutex<String>,
     pub files: Mutex<HashMap<String, Vec<u8>>>,
-    next_thread_id: AtomicU64,
+    /// The next thread or channel handle, which does not collide with the reserved stdio handles
+    next_handle: AtomicU64,
     thread_results: Mutex<HashMap<Handle, UiuaResult<Vec<Value>>>>,
+    channels: Mutex<HashMap<Handle, Channel>>,
+}
+
+/// A channel between threads
+///
+/// Threads run one at a time on the web, so this is just a queue
+#[derive(Default)]
+struct Channel {
+    values: VecDeque<Value>,
+    closed: bool,
 }
 
 impl Default for WebBackend {
@@ -27,8 +38,9 @@ impl Default for WebBackend {
             stderr: String::new().into(),
             trace: String::new().into(),
             files: HashMap::new().into(),
-            next_thread_id: 0.into(),
+            next_handle: Handle::FIRST_UNRESERVED.0.into(),
             thread_results: HashMap::new().into(),
+            channels: HashMap::new().into(),
         }
     }
 }
@@ -125,7 +137,7 @@ impl SysBackend for WebBackend {
         env: Uiua,
         f: Box<dyn FnOnce(&mut Uiua) -> UiuaResult + Send>,
     ) -> Result<Handle, String> {
-        let handle = Handle(self.next_thread_id.fetch_add(1, Ordering::SeqCst));
+        let handle = Handle(self.next_handle.fetch_add(1, Ordering::SeqCst));
         let mut env = env.clone();
         let res = f(&mut env).map(|_| env.take_stack());
         self.thread_results.lock().unwrap().insert(handle, res);
@@ -138,4 +150,50 @@ impl SysBackend for WebBackend {
             None => Err(Err("Invalid thread handle".into())),
         }
     }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(channel) = channels.get_mut(&handle) {
+            // Closed channels are only kept until they are empty
+            if channel.values.is_empty() {
+                channels.remove(&handle);
+            } else {
+                channel.closed = true;
+            }
+        }
+        Ok(())
+    }
+    fn channel(&self) -> Result<Handle, String> {
+        let handle = Handle(self.next_handle.fetch_add(1, Ordering::SeqCst));
+        self.channels
+            .lock()
+            .unwrap()
+            .insert(handle, Channel::default());
+        Ok(handle)
+    }
+    fn send(&self, handle: Handle, value: Value) -> Result<(), String> {
+        let mut channels = self.channels.lock().unwrap();
+        let channel = channels.get_mut(&handle).ok_or("Invalid channel handle")?;
+        if channel.closed {
+            return Err("The channel is closed".into());
+        }
+        channel.values.push_back(value);
+        Ok(())
+    }
+    fn recv(&self, handle: Handle) -> Result<Value, String> {
+        // No other thread can send while this one waits
+        self.try_recv(handle)?.ok_or_else(|| {
+            "The channel is empty, and waiting for values \
+            is not supported in this environment"
+                .into()
+        })
+    }
+    fn try_recv(&self, handle: Handle) -> Result<Option<Value>, String> {
+        let mut channels = self.channels.lock().unwrap();
+        let channel = channels.get_mut(&handle).ok_or("Invalid channel handle")?;
+        let value = channel.values.pop_front();
+        if channel.closed && channel.values.is_empty() {
+            channels.remove(&handle);
+        }
+        Ok(value)
+    }
 }
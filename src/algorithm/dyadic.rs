@@ -1,6 +1,12 @@
 //! Algorithms for dyadic array operations
 
-use std::{borrow::Cow, cmp::Ordering, iter::repeat, mem::take, sync::Arc};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    iter::repeat,
+    mem::{size_of, take},
+    sync::Arc,
+};
 
 use tinyvec::tiny_vec;
 
@@ -420,11 +426,11 @@ impl Value {
     pub fn reshape(&mut self, shape: &Self, env: &Uiua) -> UiuaResult {
         if let Ok(n) = shape.as_nat(env, "") {
             match self {
-                Value::Num(a) => a.reshape_scalar(n),
-                Value::Byte(a) => a.reshape_scalar(n),
-                Value::Char(a) => a.reshape_scalar(n),
-                Value::Func(a) => a.reshape_scalar(n),
-            }
+                Value::Num(a) => a.reshape_scalar(n, env),
+                Value::Byte(a) => a.reshape_scalar(n, env),
+                Value::Char(a) => a.reshape_scalar(n, env),
+                Value::Func(a) => a.reshape_scalar(n, env),
+            }?
         } else {
             let target_shape = shape.as_integers(
                 env,
@@ -443,7 +449,14 @@ impl Value {
 }
 
 impl<T: ArrayValue> Array<T> {
-    pub fn reshape_scalar(&mut self, count: usize) {
+    pub fn reshape_scalar(&mut self, count: usize, env: &Uiua) -> UiuaResult {
+        if count > 1 {
+            env.reserve(
+                (count - 1)
+                    .saturating_mul(self.data.len())
+                    .saturating_mul(size_of::<T>()),
+            )?;
+        }
         self.data.modify(|data| {
             if count == 0 {
                 data.clear();
@@ -456,6 +469,7 @@ impl<T: ArrayValue> Array<T> {
             }
         });
         self.shape.insert(0, count);
+        Ok(())
     }
     pub fn reshape(&mut self, dims: &[isize], env: &Uiua) -> UiuaResult {
         let mut neg_count = 0;
@@ -532,6 +546,7 @@ impl<T: ArrayValue> Array<T> {
         let target_len: usize = shape.iter().product();
         self.shape = shape;
         if self.data.len() < target_len {
+            env.reserve((target_len - self.data.len()).saturating_mul(size_of::<T>()))?;
             if let Some(fill) = env.fill::<T>() {
                 let start = self.data.len();
                 self.data.modify(|data| {
@@ -563,10 +578,10 @@ impl Value {
         )?;
         Ok(if self.rank() == 0 {
             match kept {
-                Value::Num(a) => a.scalar_keep(counts[0]).into(),
-                Value::Byte(a) => a.scalar_keep(counts[0]).into(),
-                Value::Char(a) => a.scalar_keep(counts[0]).into(),
-                Value::Func(a) => a.scalar_keep(counts[0]).into(),
+                Value::Num(a) => a.scalar_keep(counts[0], env)?.into(),
+                Value::Byte(a) => a.scalar_keep(counts[0], env)?.into(),
+                Value::Char(a) => a.scalar_keep(counts[0], env)?.into(),
+                Value::Func(a) => a.scalar_keep(counts[0], env)?.into(),
             }
         } else {
             match kept {
@@ -604,41 +619,46 @@ impl Value {
 }
 
 impl<T: ArrayValue> Array<T> {
-    pub fn scalar_keep(mut self, count: usize) -> Self {
+    pub fn scalar_keep(mut self, count: usize, env: &Uiua) -> UiuaResult<Self> {
         // Scalar kept
         if self.rank() == 0 {
+            env.reserve(count.saturating_mul(size_of::<T>()))?;
             self.shape.push(count);
             self.data.modify(|data| {
                 let value = data[0].clone();
                 data.clear();
-                for _ in 0..count {
-                    data.push(value.clone());
-                }
+                data.reserve(count);
+                data.extend((0..count).map(|_| value.clone()));
             });
             self.validate_shape();
-            return self;
+            return Ok(self);
         }
         // Keep nothing
         if count == 0 {
             self.data = CowSlice::new();
             self.shape[0] = 0;
-            return self;
+            return Ok(self);
         }
         // Keep 1 is a no-op
         if count == 1 {
-            return self;
+            return Ok(self);
         }
         // Keep ≥2 is a repeat
+        env.reserve(
+            (count - 1)
+                .saturating_mul(self.data.len())
+                .saturating_mul(size_of::<T>()),
+        )?;
         self.shape[0] *= count;
-        let old_data = self.data.clone();
         self.data.modify(|data| {
-            data.reserve(data.len() * count);
+            let old_data = data.clone();
+            data.reserve(old_data.len() * (count - 1));
             for _ in 1..count {
                 data.extend_from_slice(&old_data);
             }
         });
         self.validate_shape();
-        self
+        Ok(self)
     }
     pub fn list_keep(mut self, counts: &[usize], env: &Uiua) -> UiuaResult<Self> {
         let mut amount = Cow::Borrowed(counts);
@@ -988,6 +1008,12 @@ impl<T: ArrayValue> Array<T> {
                 let row_count = self.row_count();
                 let abs_taking = taking.unsigned_abs();
                 let mut filled = false;
+                if abs_taking > row_count && T::get_fill(env).is_some() {
+                    env.reserve(
+                        ((abs_taking - row_count).saturating_mul(row_len))
+                            .saturating_mul(size_of::<T>()),
+                    )?;
+                }
                 self.data.modify(|data| {
                     if taking >= 0 {
                         if abs_taking > row_count {
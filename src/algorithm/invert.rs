@@ -3,6 +3,7 @@
 use std::{cell::RefCell, collections::HashMap, fmt};
 
 use crate::{
+    algorithm::pervade::unfused,
     check::instrs_signature,
     function::{Function, Instr, TempKind},
     primitive::Primitive,
@@ -30,6 +31,9 @@ pub(crate) fn invert_instrs(instrs: &[Instr]) -> Option<Vec<Instr>> {
     if instrs.is_empty() {
         return Some(Vec::new());
     }
+    if let Some(instrs) = unfused(instrs) {
+        return invert_instrs(&instrs);
+    }
 
     thread_local! {
         static INVERT_CACHE: RefCell<HashMap<Vec<Instr>, Option<Vec<Instr>>>> = RefCell::new(HashMap::new());
@@ -132,6 +136,9 @@ pub(crate) fn under_instrs(instrs: &[Instr]) -> Option<Under> {
     if instrs.is_empty() {
         return Some((Vec::new(), Vec::new()));
     }
+    if let Some(instrs) = unfused(instrs) {
+        return under_instrs(&instrs);
+    }
 
     thread_local! {
         static UNDER_CACHE: RefCell<HashMap<Vec<Instr>, Option<Under>>> = RefCell::new(HashMap::new());
@@ -1,6 +1,10 @@
 //! Algorithms for looping modifiers
 
-use std::ops::{Add, Div, Mul, Sub};
+use std::{
+    collections::VecDeque,
+    mem::size_of,
+    ops::{Add, Div, Mul, Sub},
+};
 
 use tinyvec::tiny_vec;
 
@@ -8,6 +12,7 @@ use crate::{
     algorithm::pervade::bin_pervade_generic,
     array::{Array, ArrayValue, FormatShape, Shape},
     cowslice::cowslice,
+    function::Instr,
     primitive::Primitive,
     run::{ArrayArg, FunctionArg},
     value::Value,
@@ -33,7 +38,7 @@ pub fn reduce(env: &mut Uiua) -> UiuaResult {
             Primitive::Div => fast_reduce(nums, 1.0, flip(Div::div)),
             Primitive::Max => fast_reduce(nums, f64::NEG_INFINITY, f64::max),
             Primitive::Min => fast_reduce(nums, f64::INFINITY, f64::min),
-            _ => return generic_fold1(f, Value::Num(nums), None, env),
+            _ => return generic_fold1(f, Value::Num(nums).into_rows(), None, env),
         }),
         (Some((prim, flipped)), Value::Byte(bytes)) => env.push(match prim {
             Primitive::Add => fast_reduce(bytes, 0.0, |a, b| a + f64::from(b)),
@@ -44,9 +49,9 @@ pub fn reduce(env: &mut Uiua) -> UiuaResult {
             Primitive::Div => fast_reduce(bytes, 1.0, |a, b| f64::from(b) / a),
             Primitive::Max => fast_reduce(bytes, f64::NEG_INFINITY, |a, b| a.max(f64::from(b))),
             Primitive::Min => fast_reduce(bytes, f64::INFINITY, |a, b| a.min(f64::from(b))),
-            _ => return generic_fold1(f, Value::Byte(bytes), None, env),
+            _ => return generic_fold1(f, Value::Byte(bytes).into_rows(), None, env),
         }),
-        (_, xs) => generic_fold1(f, xs, None, env)?,
+        (_, xs) => generic_fold1(f, xs.into_rows(), None, env)?,
     }
     Ok(())
 }
@@ -104,13 +109,18 @@ pub fn fold(env: &mut Uiua) -> UiuaResult {
         0..=2 => {
             let acc = env.pop(ArrayArg(1))?;
             let xs = env.pop(ArrayArg(2))?;
-            generic_fold1(f, xs, Some(acc), env)
+            generic_fold1(f, xs.into_rows(), Some(acc), env)
         }
         _ => generic_fold_n(f, env),
     }
 }
 
-fn generic_fold1(f: Value, xs: Value, init: Option<Value>, env: &mut Uiua) -> UiuaResult {
+fn generic_fold1(
+    f: Value,
+    rows: impl Iterator<Item = Value>,
+    init: Option<Value>,
+    env: &mut Uiua,
+) -> UiuaResult {
     let sig = f.signature();
     if sig.outputs > 1 {
         return Err(env.error(format!(
@@ -121,8 +131,7 @@ fn generic_fold1(f: Value, xs: Value, init: Option<Value>, env: &mut Uiua) -> Ui
     let args = sig.args;
     match args {
         0 | 1 => {
-            let rows = init.into_iter().chain(xs.into_rows());
-            for row in rows {
+            for row in init.into_iter().chain(rows) {
                 env.push(row);
                 if env.call_catch_break(f.clone())? {
                     let reduced = if args == 0 {
@@ -137,7 +146,7 @@ fn generic_fold1(f: Value, xs: Value, init: Option<Value>, env: &mut Uiua) -> Ui
             }
         }
         2 => {
-            let mut rows = xs.into_rows();
+            let mut rows = rows;
             let mut acc = init
                 .or_else(|| rows.next())
                 .ok_or_else(|| env.error("Cannot reduce empty array"))?;
@@ -355,16 +364,21 @@ pub fn each(env: &mut Uiua) -> UiuaResult {
 fn each1_1(f: Value, xs: Value, env: &mut Uiua) -> UiuaResult {
     let mut new_values = Vec::with_capacity(xs.flat_len());
     let mut new_shape = Shape::from(xs.shape());
-    let mut old_values = xs.into_flat_values();
-    for val in old_values.by_ref() {
-        env.push(val);
-        let broke = env.call_catch_break(f.clone())?;
-        new_values.push(env.pop("each's function result")?);
-        if broke {
-            for row in old_values {
-                new_values.push(row);
+    if env.can_call_parallel(&f, &xs, xs.flat_len()) {
+        let values = xs.into_flat_values().collect();
+        new_values = env.call_parallel(&f, values, "each's function result")?;
+    } else {
+        let mut old_values = xs.into_flat_values();
+        for val in old_values.by_ref() {
+            env.push(val);
+            let broke = env.call_catch_break(f.clone())?;
+            new_values.push(env.pop("each's function result")?);
+            if broke {
+                for row in old_values {
+                    new_values.push(row);
+                }
+                break;
             }
-            break;
         }
     }
     let mut eached = Value::from_row_values(new_values, env)?;
@@ -500,7 +514,7 @@ pub fn rows(env: &mut Uiua) -> UiuaResult {
             if output {
                 rows1_1(f, xs, env)
             } else {
-                rows1_0(f, xs, env)
+                rows1_0(f, xs.into_rows(), env)
             }
         }
         2 => {
@@ -527,8 +541,18 @@ pub fn rows(env: &mut Uiua) -> UiuaResult {
 }
 
 fn rows1_1(f: Value, xs: Value, env: &mut Uiua) -> UiuaResult {
-    let mut new_rows = Vec::with_capacity(xs.row_count());
-    let mut old_rows = xs.into_rows();
+    if !env.can_call_parallel(&f, &xs, xs.row_count()) {
+        return rows_iter1_1(f, xs.into_rows(), env);
+    }
+    let new_rows = env.call_parallel(&f, xs.into_rows().collect(), "rows' function result")?;
+    let res = Value::from_row_values(new_rows, env)?;
+    env.push(res);
+    Ok(())
+}
+
+fn rows_iter1_1(f: Value, rows: impl Iterator<Item = Value>, env: &mut Uiua) -> UiuaResult {
+    let mut new_rows = Vec::with_capacity(rows.size_hint().0);
+    let mut old_rows = rows;
     for row in old_rows.by_ref() {
         env.push(row);
         let broke = env.call_catch_break(f.clone())?;
@@ -545,8 +569,8 @@ fn rows1_1(f: Value, xs: Value, env: &mut Uiua) -> UiuaResult {
     Ok(())
 }
 
-fn rows1_0(f: Value, xs: Value, env: &mut Uiua) -> UiuaResult {
-    for row in xs.into_rows() {
+fn rows1_0(f: Value, rows: impl Iterator<Item = Value>, env: &mut Uiua) -> UiuaResult {
+    for row in rows {
         env.push(row);
         let broke = env.call_catch_break(f.clone())?;
         if broke {
@@ -556,6 +580,170 @@ fn rows1_0(f: Value, xs: Value, env: &mut Uiua) -> UiuaResult {
     Ok(())
 }
 
+/// Run `≡f◫` or `/f◫` with a scalar window size without materializing the windows
+///
+/// `instrs` should start at a [`Primitive::Windows`] instruction.
+/// Returns `None` if the instructions or the values on the stack do not fit the pattern.
+pub(crate) fn lazy_windows(instrs: &[Instr], env: &mut Uiua) -> Option<UiuaResult> {
+    let [Instr::Prim(Primitive::Windows, _), Instr::Push(f), Instr::Prim(prim @ (Primitive::Rows | Primitive::Reduce), span), ..] =
+        instrs
+    else {
+        return None;
+    };
+    let sig = f.signature();
+    match prim {
+        Primitive::Rows if sig.args == 1 && sig.outputs <= 1 => {}
+        Primitive::Reduce if sig.args == 2 && sig.outputs == 1 => {}
+        _ => return None,
+    }
+    let [.., xs, size] = env.stack.as_slice() else {
+        return None;
+    };
+    let size = size.as_nat(env, "").ok()?;
+    if xs.rank() == 0 || size == 0 || size > xs.row_count() {
+        return None;
+    }
+    env.stack.pop();
+    let xs = env.stack.pop().unwrap();
+    let f = Value::clone(f);
+    Some((|| {
+        env.push_span(*span, Some(*prim));
+        if *prim == Primitive::Rows {
+            rows_windows(f, size, xs, env)?;
+        } else {
+            reduce_windows(f, size, xs, env)?;
+        }
+        env.pop_span();
+        Ok(())
+    })())
+}
+
+fn rows_windows(f: Value, size: usize, xs: Value, env: &mut Uiua) -> UiuaResult {
+    let reduced = f.as_function().and_then(|f| match f.instrs.as_slice() {
+        [Instr::Push(g), Instr::Prim(Primitive::Reduce, _)] => g.as_flipped_primitive(),
+        _ => None,
+    });
+    if let Some(res) = reduced.and_then(|(prim, _)| sliding_reduce(prim, size, &xs)) {
+        env.push(res);
+        return Ok(());
+    }
+    if f.signature().outputs == 1 {
+        rows_iter1_1(f, row_windows(xs, size), env)
+    } else {
+        rows1_0(f, row_windows(xs, size), env)
+    }
+}
+
+fn reduce_windows(f: Value, size: usize, xs: Value, env: &mut Uiua) -> UiuaResult {
+    // Reducing the windows combines each position of a window across all windows,
+    // which is the same as reducing windows of the complementary size
+    let count = xs.row_count() + 1 - size;
+    if let Some(res) =
+        (f.as_flipped_primitive()).and_then(|(prim, _)| sliding_reduce(prim, count, &xs))
+    {
+        env.push(res);
+        return Ok(());
+    }
+    generic_fold1(f, row_windows(xs, size), None, env)
+}
+
+/// Iterate over the windows of some number of rows of an array
+///
+/// The windows share the array's data, so it is not copied.
+fn row_windows(xs: Value, size: usize) -> impl Iterator<Item = Value> {
+    fn window<T: ArrayValue>(arr: &Array<T>, start: usize, size: usize) -> Array<T> {
+        let row_len = arr.row_len();
+        let mut shape = arr.shape.clone();
+        shape[0] = size;
+        Array::new(
+            shape,
+            arr.data.slice(start * row_len..(start + size) * row_len),
+        )
+    }
+    let count = xs.row_count() + 1 - size;
+    (0..count).map(move |i| {
+        xs.generic_ref(
+            |arr| window(arr, i, size).into(),
+            |arr| window(arr, i, size).into(),
+            |arr| window(arr, i, size).into(),
+            |arr| window(arr, i, size).into(),
+        )
+    })
+}
+
+/// Reduce each window of some number of rows with [`Primitive::Add`],
+/// [`Primitive::Max`], or [`Primitive::Min`] in a single pass
+///
+/// Returns `None` for other primitives and for values that the single pass
+/// would not handle the same way as reducing each window.
+fn sliding_reduce(prim: Primitive, size: usize, xs: &Value) -> Option<Value> {
+    let converted;
+    let arr = match xs {
+        Value::Num(arr) => arr,
+        Value::Byte(arr) => {
+            converted = arr.convert_ref();
+            &converted
+        }
+        _ => return None,
+    };
+    let data = &arr.data;
+    match prim {
+        Primitive::Add => {}
+        Primitive::Max | Primitive::Min if !data.iter().any(|x| x.is_nan()) => {}
+        _ => return None,
+    }
+    // A running sum is only exact if every partial sum is an integer that fits in an f64.
+    // Otherwise, each window is summed separately.
+    let running_sum = data.iter().all(|x| x.fract() == 0.0)
+        && data.iter().map(|x| x.abs()).sum::<f64>() <= (1u64 << 53) as f64;
+    let row_count = arr.row_count();
+    let row_len = arr.row_len();
+    let count = row_count + 1 - size;
+    let mut new_data = vec![0.0; count * row_len];
+    let mut deque = VecDeque::new();
+    for j in 0..row_len {
+        let x = |i: usize| data[i * row_len + j];
+        if prim == Primitive::Add && running_sum {
+            let mut sum = (1..size).map(x).fold(x(0), Add::add);
+            new_data[j] = sum;
+            for i in 1..count {
+                sum += x(i + size - 1) - x(i - 1);
+                new_data[i * row_len + j] = sum;
+            }
+        } else if prim == Primitive::Add {
+            for i in 0..count {
+                new_data[i * row_len + j] = (i + 1..i + size).map(x).fold(x(i), Add::add);
+            }
+        } else {
+            // The front of the deque is the index of the extreme value of the current window
+            let beats = |a: f64, b: f64| {
+                if prim == Primitive::Max {
+                    a >= b
+                } else {
+                    a <= b
+                }
+            };
+            deque.clear();
+            for i in 0..row_count {
+                while deque.back().is_some_and(|&k| beats(x(i), x(k))) {
+                    deque.pop_back();
+                }
+                deque.push_back(i);
+                if i + 1 >= size {
+                    let start = i + 1 - size;
+                    while deque[0] < start {
+                        deque.pop_front();
+                    }
+                    new_data[start * row_len + j] = x(deque[0]);
+                }
+            }
+        }
+    }
+    let mut shape = arr.shape.clone();
+    shape[0] = count;
+    Some(Array::new(shape, new_data).into())
+}
+
 fn rows2_1(f: Value, xs: Value, ys: Value, env: &mut Uiua) -> UiuaResult {
     if xs.row_count() != ys.row_count() {
         return Err(env.error(format!(
@@ -728,46 +916,56 @@ pub fn table(env: &mut Uiua) -> UiuaResult {
     let ys = env.pop(ArrayArg(2))?;
     match (f.as_flipped_primitive(), xs, ys) {
         (Some((prim, flipped)), Value::Num(xs), Value::Num(ys)) => {
-            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env) {
+            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env)? {
                 return generic_table(f, Value::Num(xs), Value::Num(ys), env);
             }
         }
         (Some((prim, flipped)), Value::Num(xs), Value::Byte(ys)) => {
             let ys = ys.convert();
-            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env) {
+            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env)? {
                 return generic_table(f, Value::Num(xs), Value::Num(ys), env);
             }
         }
         (Some((prim, flipped)), Value::Byte(xs), Value::Num(ys)) => {
             let xs = xs.convert();
-            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env) {
+            if let Err((xs, ys)) = table_nums(prim, flipped, xs, ys, env)? {
                 return generic_table(f, Value::Num(xs), Value::Num(ys), env);
             }
         }
         (Some((prim, flipped)), Value::Byte(xs), Value::Byte(ys)) => match prim {
-            Primitive::Eq => env.push(fast_table(xs, ys, bin_bool(|x, y| x == y))),
-            Primitive::Ne => env.push(fast_table(xs, ys, bin_bool(|x, y| x != y))),
-            Primitive::Lt if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x < y))),
-            Primitive::Lt => env.push(fast_table(xs, ys, bin_bool(|x, y| y < x))),
-            Primitive::Gt if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x > y))),
-            Primitive::Gt => env.push(fast_table(xs, ys, bin_bool(|x, y| y > x))),
-            Primitive::Le if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x <= y))),
-            Primitive::Le => env.push(fast_table(xs, ys, bin_bool(|x, y| y <= x))),
-            Primitive::Ge if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x >= y))),
-            Primitive::Ge => env.push(fast_table(xs, ys, bin_bool(|x, y| y >= x))),
-            Primitive::Add => env.push(fast_table(xs, ys, |a, b| f64::from(a) + f64::from(b))),
+            Primitive::Eq => env.push(fast_table(xs, ys, bin_bool(|x, y| x == y), env)?),
+            Primitive::Ne => env.push(fast_table(xs, ys, bin_bool(|x, y| x != y), env)?),
+            Primitive::Lt if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x < y), env)?),
+            Primitive::Lt => env.push(fast_table(xs, ys, bin_bool(|x, y| y < x), env)?),
+            Primitive::Gt if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x > y), env)?),
+            Primitive::Gt => env.push(fast_table(xs, ys, bin_bool(|x, y| y > x), env)?),
+            Primitive::Le if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x <= y), env)?),
+            Primitive::Le => env.push(fast_table(xs, ys, bin_bool(|x, y| y <= x), env)?),
+            Primitive::Ge if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x >= y), env)?),
+            Primitive::Ge => env.push(fast_table(xs, ys, bin_bool(|x, y| y >= x), env)?),
+            Primitive::Add => {
+                env.push(fast_table(xs, ys, |a, b| f64::from(a) + f64::from(b), env)?)
+            }
             Primitive::Sub if flipped => {
-                env.push(fast_table(xs, ys, |a, b| f64::from(a) - f64::from(b)))
+                env.push(fast_table(xs, ys, |a, b| f64::from(a) - f64::from(b), env)?)
+            }
+            Primitive::Sub => {
+                env.push(fast_table(xs, ys, |a, b| f64::from(b) - f64::from(a), env)?)
+            }
+            Primitive::Mul => {
+                env.push(fast_table(xs, ys, |a, b| f64::from(a) * f64::from(b), env)?)
             }
-            Primitive::Sub => env.push(fast_table(xs, ys, |a, b| f64::from(b) - f64::from(a))),
-            Primitive::Mul => env.push(fast_table(xs, ys, |a, b| f64::from(a) * f64::from(b))),
             Primitive::Div if flipped => {
-                env.push(fast_table(xs, ys, |a, b| f64::from(a) / f64::from(b)))
+                env.push(fast_table(xs, ys, |a, b| f64::from(a) / f64::from(b), env)?)
+            }
+            Primitive::Div => {
+                env.push(fast_table(xs, ys, |a, b| f64::from(b) / f64::from(a), env)?)
+            }
+            Primitive::Min => env.push(fast_table(xs, ys, u8::min, env)?),
+            Primitive::Max => env.push(fast_table(xs, ys, u8::max, env)?),
+            Primitive::Join | Primitive::Couple => {
+                env.push(fast_table_join_or_couple(xs, ys, env)?)
             }
-            Primitive::Div => env.push(fast_table(xs, ys, |a, b| f64::from(b) / f64::from(a))),
-            Primitive::Min => env.push(fast_table(xs, ys, u8::min)),
-            Primitive::Max => env.push(fast_table(xs, ys, u8::max)),
-            Primitive::Join | Primitive::Couple => env.push(fast_table_join_or_couple(xs, ys)),
             _ => generic_table(f, Value::Byte(xs), Value::Byte(ys), env)?,
         },
         (_, xs, ys) => generic_table(f, xs, ys, env)?,
@@ -775,44 +973,46 @@ pub fn table(env: &mut Uiua) -> UiuaResult {
     Ok(())
 }
 
-#[allow(clippy::result_large_err)]
+#[allow(clippy::result_large_err, clippy::type_complexity)]
 fn table_nums(
     prim: Primitive,
     flipped: bool,
     xs: Array<f64>,
     ys: Array<f64>,
     env: &mut Uiua,
-) -> Result<(), (Array<f64>, Array<f64>)> {
+) -> UiuaResult<Result<(), (Array<f64>, Array<f64>)>> {
     match prim {
-        Primitive::Eq => env.push(fast_table(xs, ys, bin_bool(|x, y| x == y))),
-        Primitive::Ne => env.push(fast_table(xs, ys, bin_bool(|x, y| x != y))),
-        Primitive::Lt if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x < y))),
-        Primitive::Lt => env.push(fast_table(xs, ys, bin_bool(|x, y| y < x))),
-        Primitive::Gt if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x > y))),
-        Primitive::Gt => env.push(fast_table(xs, ys, bin_bool(|x, y| y > x))),
-        Primitive::Le if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x <= y))),
-        Primitive::Le => env.push(fast_table(xs, ys, bin_bool(|x, y| y <= x))),
-        Primitive::Ge if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x >= y))),
-        Primitive::Ge => env.push(fast_table(xs, ys, bin_bool(|x, y| y >= x))),
-        Primitive::Add => env.push(fast_table(xs, ys, Add::add)),
-        Primitive::Sub if flipped => env.push(fast_table(xs, ys, Sub::sub)),
-        Primitive::Sub => env.push(fast_table(xs, ys, flip(Sub::sub))),
-        Primitive::Mul => env.push(fast_table(xs, ys, Mul::mul)),
-        Primitive::Div if flipped => env.push(fast_table(xs, ys, Div::div)),
-        Primitive::Div => env.push(fast_table(xs, ys, flip(Div::div))),
-        Primitive::Min => env.push(fast_table(xs, ys, f64::min)),
-        Primitive::Max => env.push(fast_table(xs, ys, f64::max)),
-        Primitive::Join | Primitive::Couple => env.push(fast_table_join_or_couple(xs, ys)),
-        _ => return Err((xs, ys)),
+        Primitive::Eq => env.push(fast_table(xs, ys, bin_bool(|x, y| x == y), env)?),
+        Primitive::Ne => env.push(fast_table(xs, ys, bin_bool(|x, y| x != y), env)?),
+        Primitive::Lt if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x < y), env)?),
+        Primitive::Lt => env.push(fast_table(xs, ys, bin_bool(|x, y| y < x), env)?),
+        Primitive::Gt if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x > y), env)?),
+        Primitive::Gt => env.push(fast_table(xs, ys, bin_bool(|x, y| y > x), env)?),
+        Primitive::Le if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x <= y), env)?),
+        Primitive::Le => env.push(fast_table(xs, ys, bin_bool(|x, y| y <= x), env)?),
+        Primitive::Ge if flipped => env.push(fast_table(xs, ys, bin_bool(|x, y| x >= y), env)?),
+        Primitive::Ge => env.push(fast_table(xs, ys, bin_bool(|x, y| y >= x), env)?),
+        Primitive::Add => env.push(fast_table(xs, ys, Add::add, env)?),
+        Primitive::Sub if flipped => env.push(fast_table(xs, ys, Sub::sub, env)?),
+        Primitive::Sub => env.push(fast_table(xs, ys, flip(Sub::sub), env)?),
+        Primitive::Mul => env.push(fast_table(xs, ys, Mul::mul, env)?),
+        Primitive::Div if flipped => env.push(fast_table(xs, ys, Div::div, env)?),
+        Primitive::Div => env.push(fast_table(xs, ys, flip(Div::div), env)?),
+        Primitive::Min => env.push(fast_table(xs, ys, f64::min, env)?),
+        Primitive::Max => env.push(fast_table(xs, ys, f64::max, env)?),
+        Primitive::Join | Primitive::Couple => env.push(fast_table_join_or_couple(xs, ys, env)?),
+        _ => return Ok(Err((xs, ys))),
     }
-    Ok(())
+    Ok(Ok(()))
 }
 
 fn fast_table<A: ArrayValue, B: ArrayValue, C: ArrayValue>(
     a: Array<A>,
     b: Array<B>,
     f: impl Fn(A, B) -> C,
-) -> Array<C> {
+    env: &Uiua,
+) -> UiuaResult<Array<C>> {
+    env.reserve((a.data.len().saturating_mul(b.data.len())).saturating_mul(size_of::<C>()))?;
     let mut new_data = Vec::with_capacity(a.data.len() * b.data.len());
     for x in a.data {
         for y in b.data.iter().cloned() {
@@ -821,10 +1021,15 @@ fn fast_table<A: ArrayValue, B: ArrayValue, C: ArrayValue>(
     }
     let mut new_shape = a.shape;
     new_shape.extend_from_slice(&b.shape);
-    Array::new(new_shape, new_data)
+    Ok(Array::new(new_shape, new_data))
 }
 
-fn fast_table_join_or_couple<T: ArrayValue>(a: Array<T>, b: Array<T>) -> Array<T> {
+fn fast_table_join_or_couple<T: ArrayValue>(
+    a: Array<T>,
+    b: Array<T>,
+    env: &Uiua,
+) -> UiuaResult<Array<T>> {
+    env.reserve((a.data.len().saturating_mul(b.data.len())).saturating_mul(2 * size_of::<T>()))?;
     let mut new_data = Vec::with_capacity(a.data.len() * b.data.len() * 2);
     for x in a.data {
         for y in b.data.iter().cloned() {
@@ -835,7 +1040,7 @@ fn fast_table_join_or_couple<T: ArrayValue>(a: Array<T>, b: Array<T>) -> Array<T
     let mut new_shape = a.shape;
     new_shape.extend_from_slice(&b.shape);
     new_shape.push(2);
-    Array::new(new_shape, new_data)
+    Ok(Array::new(new_shape, new_data))
 }
 
 fn generic_table(f: Value, xs: Value, ys: Value, env: &mut Uiua) -> UiuaResult {
@@ -848,6 +1053,7 @@ fn generic_table(f: Value, xs: Value, ys: Value, env: &mut Uiua) -> UiuaResult {
     }
     let mut new_shape = Shape::from(xs.shape());
     new_shape.extend_from_slice(ys.shape());
+    env.reserve((xs.flat_len().saturating_mul(ys.flat_len())).saturating_mul(size_of::<Value>()))?;
     let mut items = Vec::with_capacity(xs.flat_len() * ys.flat_len());
     let y_values = ys.into_flat_values().collect::<Vec<_>>();
     for x in xs.into_flat_values() {
@@ -1325,3 +1531,104 @@ where
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use crate::Uiua;
+
+    #[test]
+    fn lazy_windows() {
+        // Binding the windows first materializes them, which gives the expected results
+        let mut env = Uiua::with_native_sys();
+        for (f, xs) in [
+            ("≡/+", "[1 5 2 4 3]"),
+            ("≡/↥", "[1 5 2 4 3]"),
+            ("≡/↧", "[1 5 2 4 3]"),
+            ("≡/+", "↯4_3 ⇡12"),
+            ("≡/↥", "↯4_3 [3 1 4 1 5 9 2 6 5 3 5 8]"),
+            ("≡/+", "[1 ∞ 2 3]"),
+            ("≡/+", "[1e16 1 1 1 1]"),
+            ("≡/+", "[0.1 0.2 0.3 0.4]"),
+            ("≡/↥", "[1 NaN 2 3]"),
+            ("≡⊢", "\"abcde\""),
+            ("≡(/-)", "[1 5 2 4 3]"),
+            ("/+", "[1 5 2 4 3]"),
+            ("/↧", "↯4_3 [3 1 4 1 5 9 2 6 5 3 5 8]"),
+            ("/⊂", "[1 5 2 4 3]"),
+        ] {
+            let lazy = format!("{f}◫3 {xs}");
+            let materialized = format!("W ← ◫3 {xs}\n{f}W");
+            env.load_str(&lazy).unwrap();
+            let lazy_stack = env.take_stack();
+            env.load_str(&materialized).unwrap();
+            assert_eq!(lazy_stack, env.take_stack(), "{lazy}");
+        }
+        // Running sums lose precision with large values
+        env.load_str("≡/+◫2 [1e16 1 1 1]").unwrap();
+        let expected: crate::value::Value = vec![1e16, 2.0, 2.0].into();
+        assert_eq!(env.take_stack(), [expected]);
+        // Windows that would not fit in the memory limit if materialized
+        let mut env = Uiua::with_native_sys().with_memory_limit(1 << 24);
+        env.load_str("⧻≡/+◫1000 ⇡100000\n⧻/↥◫1000 ⇡100000\n⧻≡⊢◫1000 ⇡5000")
+            .unwrap();
+        let expected: [crate::value::Value; 3] = [99001.0.into(), 1000.0.into(), 4001.0.into()];
+        assert_eq!(env.take_stack(), expected);
+    }
+
+    #[test]
+    fn parallel_loops() {
+        // Use several threads even if the machine only has one core
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        // Each loop is big enough to be run in parallel
+        for (f, xs) in [
+            ("(/+×.)", "↯10000_10 ⇡100000"),
+            ("(⊂.)", "⇡20000"),
+            ("(≡(+1))", "↯1000_10_10 ⇡100000"),
+            ("(⍤:<5000.)", "⇡10000"),
+            ("(⍤:≠7500.)", "⇡20000"),
+        ] {
+            let mut env = Uiua::with_native_sys();
+            env.load_str(&format!("{f}\n{xs}")).unwrap();
+            let xs = env.pop(1).unwrap();
+            let f = env.pop(2).unwrap();
+            let count = xs.row_count();
+            assert!(
+                pool.install(|| env.can_call_parallel(&f, &xs, count)),
+                "{f}"
+            );
+        }
+        for code in [
+            "≡(/+×.) ↯10000_10 ⇡100000",
+            "∵(⊂.) ⇡20000",
+            "≡(≡(+1)) ↯1000_10_10 ⇡100000",
+            "⬚0≡(⇡) ◿10 ⇡20000",
+            // Several chunks fail, and the error must be the one from the earliest row
+            "≡(⍤:<5000.) ⇡10000",
+            "∵(⍤:≠7500.) ⇡20000",
+        ] {
+            let run = |parallel: bool| {
+                let mut env = Uiua::with_native_sys().with_parallel_loops(parallel);
+                match env.load_str(code) {
+                    Ok(()) => Ok(env.take_stack()),
+                    Err(e) => Err(e.to_string()),
+                }
+            };
+            let sequential = run(false);
+            let parallel = pool.install(|| run(true));
+            assert_eq!(sequential, parallel, "{code}");
+        }
+        // Small loops are not worth splitting
+        let mut env = Uiua::with_native_sys();
+        env.load_str("(/+×.)\n↯10_10 ⇡100").unwrap();
+        let xs = env.pop(1).unwrap();
+        let f = env.pop(2).unwrap();
+        assert!(!pool.install(|| env.can_call_parallel(&f, &xs, 10)));
+        // Traces must be printed in order
+        let mut env = Uiua::with_native_sys();
+        env.load_str("(~×2)").unwrap();
+        assert!(!env.take_stack()[0].is_pure());
+    }
+}
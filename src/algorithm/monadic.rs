@@ -3,6 +3,7 @@
 use std::{
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet},
+    mem::size_of,
     ptr,
     sync::Arc,
 };
@@ -73,6 +74,7 @@ fn range(shape: &[usize], env: &Uiua) -> UiuaResult<Vec<f64>> {
         }
         len = new;
     }
+    env.reserve(len.saturating_mul(size_of::<f64>()))?;
     let mut data: Vec<f64> = Vec::with_capacity(len);
     let mut curr = vec![0; shape.len()];
     loop {
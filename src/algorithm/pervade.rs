@@ -8,7 +8,9 @@ use std::{
     slice::{self, Chunks},
 };
 
-use crate::{array::*, Uiua, UiuaError, UiuaResult};
+use crate::{
+    array::*, function::Instr, primitive::Primitive, value::Value, Uiua, UiuaError, UiuaResult,
+};
 
 use super::max_shape;
 
@@ -253,6 +255,108 @@ where
     Ok(())
 }
 
+/// A step in a chain of pervasive operations that is run one element at a time
+#[derive(Clone, Copy)]
+pub(crate) enum FusedStep {
+    Monadic(fn(f64) -> f64),
+    /// A dyadic operation whose first argument is a scalar
+    Dyadic(fn(f64, f64) -> f64, f64),
+}
+
+/// A chain of pervasive operations on a number array that is run in a single loop
+///
+/// This is made by [`Uiua::optimize_instrs`] from instructions that each apply a
+/// pervasive operation to the value on top of the stack, possibly with a scalar.
+#[derive(Clone)]
+pub struct Fused {
+    pub(crate) steps: Vec<FusedStep>,
+    /// The instructions the steps were made from, which are run instead
+    /// if the value on top of the stack is not a number array
+    pub(crate) instrs: Vec<Instr>,
+}
+
+/// Get the pervasive operation at the end of some instructions that could be fused
+///
+/// Returns the step and the number of instructions it covers
+pub(crate) fn fusable_tail(instrs: &[Instr]) -> Option<(FusedStep, usize)> {
+    if let [.., Instr::Push(val), Instr::Prim(prim, _)] = instrs {
+        if let (Some(f), Value::Num(arr)) = (dyadic_num_fn(*prim), &**val) {
+            if let Some(&a) = arr.as_scalar() {
+                return Some((FusedStep::Dyadic(f, a), 2));
+            }
+        }
+    }
+    match instrs {
+        [.., Instr::Prim(prim, _)] => monadic_num_fn(*prim).map(|f| (FusedStep::Monadic(f), 1)),
+        _ => None,
+    }
+}
+
+/// Replace fused instructions with the instructions they were made from
+///
+/// Returns `None` if there are no fused instructions
+pub(crate) fn unfused(instrs: &[Instr]) -> Option<Vec<Instr>> {
+    if !instrs.iter().any(|instr| matches!(instr, Instr::Fused(_))) {
+        return None;
+    }
+    let mut unfused = Vec::with_capacity(instrs.len());
+    for instr in instrs {
+        match instr {
+            Instr::Fused(fused) => unfused.extend(fused.instrs.iter().cloned()),
+            instr => unfused.push(instr.clone()),
+        }
+    }
+    Some(unfused)
+}
+
+/// Apply a chain of pervasive operations to some numbers
+pub(crate) fn run_fused(steps: &[FusedStep], data: &mut [f64]) {
+    for x in data {
+        for step in steps {
+            *x = match *step {
+                FusedStep::Monadic(f) => f(*x),
+                FusedStep::Dyadic(f, a) => f(a, *x),
+            };
+        }
+    }
+}
+
+fn monadic_num_fn(prim: Primitive) -> Option<fn(f64) -> f64> {
+    use Primitive::*;
+    Some(match prim {
+        Not => not::num,
+        Neg => neg::num,
+        Abs => abs::num,
+        Sign => sign::num,
+        Sqrt => sqrt::num,
+        Sin => sin::num,
+        Cos => cos::num,
+        Asin => asin::num,
+        Acos => acos::num,
+        Floor => floor::num,
+        Ceil => ceil::num,
+        Round => round::num,
+        _ => return None,
+    })
+}
+
+fn dyadic_num_fn(prim: Primitive) -> Option<fn(f64, f64) -> f64> {
+    use Primitive::*;
+    Some(match prim {
+        Add => add::num_num,
+        Sub => sub::num_num,
+        Mul => mul::num_num,
+        Div => div::num_num,
+        Mod => modulus::num_num,
+        Pow => pow::num_num,
+        Log => log::num_num,
+        Min => min::num_num,
+        Max => max::num_num,
+        Atan => atan2::num_num,
+        _ => return None,
+    })
+}
+
 pub mod not {
     use super::*;
     pub fn num(a: f64) -> f64 {
@@ -844,3 +948,26 @@ fn bin_pervade_recursive_generic<A: PervasiveInput, B: PervasiveInput, C>(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{value::Value, Uiua};
+
+    #[test]
+    fn fused() {
+        // The chains are in functions so that they are not folded into constants
+        let mut env = Uiua::with_native_sys();
+        env.load_str("F ← ×2 +1 ÷2\nG ← ⌊ ¯ ×3\nF ⇡4\nG [0.5 1.5]\nF 1\n⍘F 5")
+            .unwrap();
+        let expected: [Value; 4] = [
+            vec![2.0, 3.0, 4.0, 5.0].into(),
+            vec![-2.0, -5.0].into(),
+            3.0.into(),
+            3.0.into(),
+        ];
+        assert_eq!(env.take_stack(), expected);
+        // Chains on arrays that are not numbers run one operation at a time
+        env.load_str("H ← +1 +1\nH \"ab\"").unwrap();
+        assert_eq!(env.take_stack(), [Value::from("cd")]);
+    }
+}
@@ -120,6 +120,7 @@ impl<'a> VirtualEnv<'a> {
             }
             Instr::Dynamic(f) => self.handle_sig(f.signature)?,
             Instr::DropTemp { .. } => {}
+            Instr::Fused(fused) => self.instrs(&fused.instrs)?,
             Instr::Prim(prim, _) => match prim {
                 Reduce | Scan => {
                     let f = self.pop()?;
@@ -75,6 +75,8 @@ impl Uiua {
         };
         let mut val = match instrs_signature(&instrs) {
             Ok(mut sig) => {
+                #[cfg(feature = "lsp")]
+                self.record_signature(&binding.name.span, sig, binding.signature.as_ref());
                 if let Some(declared_sig) = &binding.signature {
                     if declared_sig.value.is_superset_of(sig) {
                         sig = declared_sig.value;
@@ -133,7 +135,26 @@ impl Uiua {
         self.scope.names.insert(binding.name.value, idx);
         Ok(())
     }
-    fn compile_words(&mut self, words: Vec<Sp<Word>>, call: bool) -> UiuaResult<Vec<Instr>> {
+    #[cfg(feature = "lsp")]
+    fn record_signature(
+        &mut self,
+        span: &CodeSpan,
+        inferred: Signature,
+        declared: Option<&Sp<Signature>>,
+    ) {
+        if let Some(signatures) = &mut self.inferred_signatures {
+            signatures.push(InferredSignature {
+                span: span.clone(),
+                inferred,
+                declared: declared.map(|sig| sig.value),
+            });
+        }
+    }
+    pub(crate) fn compile_words(
+        &mut self,
+        words: Vec<Sp<Word>>,
+        call: bool,
+    ) -> UiuaResult<Vec<Instr>> {
         self.new_functions.push(Vec::new());
         self.words(words, call)?;
         if self.print_diagnostics {
@@ -142,7 +163,7 @@ impl Uiua {
             }
         }
         let instrs = self.new_functions.pop().unwrap();
-        Ok(instrs)
+        Ok(self.optimize_instrs(instrs))
     }
     fn compile_operand_words(
         &mut self,
@@ -387,6 +408,8 @@ impl Uiua {
         // Validate signature
         let sig = match instrs_signature(&instrs) {
             Ok(mut sig) => {
+                #[cfg(feature = "lsp")]
+                self.record_signature(&span, sig, func.signature.as_ref());
                 if let Some(declared_sig) = &func.signature {
                     if declared_sig.value.is_superset_of(sig) {
                         sig = declared_sig.value;
@@ -628,12 +651,12 @@ impl Uiua {
         }
 
         if call {
-            self.words(modified.operands, false)?;
+            self.modifier_operands(modified.modifier.value, modified.operands)?;
             let span = self.add_span(modified.modifier.span);
             self.push_instr(Instr::Prim(modified.modifier.value, span));
         } else {
             self.new_functions.push(Vec::new());
-            self.words(modified.operands, false)?;
+            self.modifier_operands(modified.modifier.value, modified.operands)?;
             self.primitive(
                 modified.modifier.value,
                 modified.modifier.span.clone(),
@@ -656,6 +679,13 @@ impl Uiua {
         }
         Ok(())
     }
+    fn modifier_operands(&mut self, modifier: Primitive, operands: Vec<Sp<Word>>) -> UiuaResult {
+        let fill = (modifier == Primitive::Fill) as usize;
+        self.fill_depth += fill;
+        let res = self.words(operands, false);
+        self.fill_depth -= fill;
+        res
+    }
     fn handle_primitive_deprecation(&mut self, prim: Primitive, span: &CodeSpan) {
         if let Some(suggestion) = prim.deprecation_suggestion() {
             let suggestion = if suggestion.is_empty() {
@@ -0,0 +1,316 @@
+//! Conversions between Rust types and Uiua [`Value`]s
+//!
+//! These are useful when embedding Uiua in a Rust program.
+//!
+//! ```
+//! use uiua::{FromValue, IntoValue, Uiua};
+//!
+//! let mut env = Uiua::with_native_sys();
+//! env.load_str("Avg ← ÷⧻∶/+.").unwrap();
+//! let outputs = env.call_binding("Avg", [vec![1.0, 2.0, 6.0].into_value()]).unwrap();
+//! assert_eq!(f64::from_value(outputs[0].clone()), Ok(3.0));
+//!
+//! let words = vec!["hello".to_string(), "array".to_string()];
+//! let value = words.clone().into_value();
+//! assert_eq!(Vec::<String>::from_value(value), Ok(words));
+//! ```
+
+use std::sync::Arc;
+
+use crate::{
+    array::{Array, ArrayValue, Shape},
+    function::Function,
+    value::Value,
+};
+
+/// Types that can be converted into a [`Value`]
+pub trait IntoValue: Sized {
+    /// Convert into a value
+    fn into_value(self) -> Value;
+    /// Convert a list of rows into a value
+    ///
+    /// By default, rows of the same type and shape are joined into a single array,
+    /// and any other rows are boxed.
+    fn rows_into_value(rows: Vec<Self>) -> Value {
+        let rows: Vec<Value> = rows.into_iter().map(IntoValue::into_value).collect();
+        if rows.is_empty() {
+            return Array::<f64>::new(Shape::from([0].as_slice()), Vec::new()).into();
+        }
+        let uniform = (rows.windows(2))
+            .all(|w| w[0].type_name() == w[1].type_name() && w[0].shape() == w[1].shape());
+        if uniform && !matches!(rows[0], Value::Func(_)) {
+            Value::from_row_values_infallible(rows)
+        } else {
+            boxed_rows(rows)
+        }
+    }
+}
+
+/// Types that can be converted from a [`Value`]
+///
+/// Boxed scalars are unboxed before conversion.
+pub trait FromValue: Sized {
+    /// Convert from a value
+    fn from_value(value: Value) -> Result<Self, String>;
+}
+
+fn boxed_rows(rows: Vec<Value>) -> Value {
+    rows.into_iter()
+        .map(|row| Arc::new(Function::constant(row)))
+        .collect()
+}
+
+pub(crate) fn unboxed(value: Value) -> Value {
+    match value {
+        Value::Func(arr) => match arr.into_constant() {
+            Ok(value) => unboxed(value),
+            Err(arr) => Value::Func(arr),
+        },
+        value => value,
+    }
+}
+
+fn scalar_num(value: Value, requirement: &str) -> Result<f64, String> {
+    match unboxed(value) {
+        Value::Num(arr) if arr.rank() == 0 => Ok(arr.data[0]),
+        Value::Byte(arr) if arr.rank() == 0 => Ok(arr.data[0] as f64),
+        value if value.rank() != 0 => Err(format!(
+            "Expected {requirement}, but the value has shape {}",
+            value.format_shape()
+        )),
+        value => Err(format!(
+            "Expected {requirement}, but the value is a {}",
+            value.type_name()
+        )),
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Result<Self, String> {
+        Ok(value)
+    }
+}
+
+macro_rules! scalar_into_value {
+    ($($ty:ty),* $(,)?) => {
+        $(impl IntoValue for $ty {
+            fn into_value(self) -> Value {
+                self.into()
+            }
+        })*
+    };
+}
+
+scalar_into_value!(f64, u8, char, bool, usize, i32);
+
+impl FromValue for f64 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        scalar_num(value, "a number")
+    }
+}
+
+impl FromValue for u8 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        let n = scalar_num(value, "a byte")?;
+        if n.fract() == 0.0 && (0.0..=255.0).contains(&n) {
+            Ok(n as u8)
+        } else {
+            Err(format!("Expected a byte, but the value is {n}"))
+        }
+    }
+}
+
+impl FromValue for usize {
+    fn from_value(value: Value) -> Result<Self, String> {
+        let n = scalar_num(value, "a natural number")?;
+        if n.fract() == 0.0 && n >= 0.0 {
+            Ok(n as usize)
+        } else {
+            Err(format!("Expected a natural number, but the value is {n}"))
+        }
+    }
+}
+
+impl FromValue for i32 {
+    fn from_value(value: Value) -> Result<Self, String> {
+        let n = scalar_num(value, "an integer")?;
+        if n.fract() == 0.0 && (i32::MIN as f64..=i32::MAX as f64).contains(&n) {
+            Ok(n as i32)
+        } else {
+            Err(format!("Expected an integer, but the value is {n}"))
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Value) -> Result<Self, String> {
+        let n = scalar_num(value, "a boolean")?;
+        if n == 0.0 || n == 1.0 {
+            Ok(n == 1.0)
+        } else {
+            Err(format!("Expected a boolean, but the value is {n}"))
+        }
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match unboxed(value) {
+            Value::Char(arr) if arr.rank() == 0 => Ok(arr.data[0]),
+            value => Err(format!(
+                "Expected a character, but the value is a {} array with shape {}",
+                value.type_name(),
+                value.format_shape()
+            )),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+    fn rows_into_value(rows: Vec<Self>) -> Value {
+        boxed_rows(rows.into_iter().map(Value::from).collect())
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+    fn rows_into_value(rows: Vec<Self>) -> Value {
+        boxed_rows(rows.into_iter().map(Value::from).collect())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match unboxed(value) {
+            Value::Char(arr) if arr.rank() <= 1 => Ok(arr.data.iter().collect()),
+            value => Err(format!(
+                "Expected a string, but the value is a {} array with shape {}",
+                value.type_name(),
+                value.format_shape()
+            )),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        T::rows_into_value(self)
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        let value = unboxed(value);
+        if value.rank() == 0 {
+            return Err(format!(
+                "Expected a list, but the value is a scalar {}",
+                value.type_name()
+            ));
+        }
+        value.into_rows().map(T::from_value).collect()
+    }
+}
+
+impl<T: IntoValue, const N: usize> IntoValue for [T; N] {
+    fn into_value(self) -> Value {
+        T::rows_into_value(self.into())
+    }
+}
+
+macro_rules! array_conversions {
+    ($($ty:ty, $variant:ident);* $(;)?) => {
+        $(
+            impl IntoValue for Array<$ty> {
+                fn into_value(self) -> Value {
+                    self.into()
+                }
+            }
+            impl FromValue for Array<$ty> {
+                fn from_value(value: Value) -> Result<Self, String> {
+                    match unboxed(value) {
+                        Value::$variant(arr) => Ok(arr),
+                        value => Err(format!(
+                            "Expected a {} array, but the value is a {} array",
+                            stringify!($ty),
+                            value.type_name()
+                        )),
+                    }
+                }
+            }
+            /// An array's shape and its row-major data
+            impl FromValue for (Vec<usize>, Vec<$ty>) {
+                fn from_value(value: Value) -> Result<Self, String> {
+                    let arr = Array::<$ty>::from_value(value)?;
+                    Ok((arr.shape.to_vec(), arr.data.into()))
+                }
+            }
+        )*
+    };
+}
+
+array_conversions!(u8, Byte; char, Char);
+
+impl IntoValue for Array<f64> {
+    fn into_value(self) -> Value {
+        self.into()
+    }
+}
+
+impl FromValue for Array<f64> {
+    fn from_value(value: Value) -> Result<Self, String> {
+        match unboxed(value) {
+            Value::Num(arr) => Ok(arr),
+            Value::Byte(arr) => Ok(arr.convert()),
+            value => Err(format!(
+                "Expected a numeric array, but the value is a {} array",
+                value.type_name()
+            )),
+        }
+    }
+}
+
+/// An array's shape and its row-major data
+impl FromValue for (Vec<usize>, Vec<f64>) {
+    fn from_value(value: Value) -> Result<Self, String> {
+        let arr = Array::<f64>::from_value(value)?;
+        Ok((arr.shape.to_vec(), arr.data.into()))
+    }
+}
+
+/// An array's shape and its row-major data
+///
+/// Fails if the number of elements in the shape does not match the length of the data.
+///
+/// ```
+/// use uiua::value::Value;
+///
+/// assert!(Value::try_from((vec![2, 3], vec![1.0; 6])).is_ok());
+/// assert!(Value::try_from((vec![2, 3], vec![1.0; 5])).is_err());
+/// ```
+impl<T: ArrayValue> TryFrom<(Vec<usize>, Vec<T>)> for Value
+where
+    Value: From<Array<T>>,
+{
+    type Error = String;
+    fn try_from((shape, data): (Vec<usize>, Vec<T>)) -> Result<Self, Self::Error> {
+        let len: usize = shape.iter().product();
+        if len != data.len() {
+            return Err(format!(
+                "Shape {shape:?} has {len} elements, but the data has {}",
+                data.len()
+            ));
+        }
+        Ok(Array::new(Shape::from(shape.as_slice()), data).into())
+    }
+}
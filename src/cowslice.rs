@@ -4,6 +4,7 @@ use std::{
     fmt,
     hash::{Hash, Hasher},
     iter::{Skip, Take},
+    mem::{size_of, take},
     ops::{Bound, Deref, DerefMut, RangeBounds},
 };
 
@@ -19,6 +20,8 @@ macro_rules! cowslice {
 pub(crate) use cowslice;
 use ecow::EcoVec;
 
+use crate::memory;
+
 pub struct CowSlice<T> {
     data: EcoVec<T>,
     start: u32,
@@ -32,6 +35,38 @@ impl<T> CowSlice<T> {
     pub fn truncate(&mut self, len: usize) {
         self.end = (self.start + len as u32).min(self.end);
     }
+    /// Create a slice over all of some data, tracking its memory
+    fn tracked(data: EcoVec<T>) -> Self {
+        memory::track(alloc_size(&data));
+        Self {
+            start: 0,
+            end: data.len() as u32,
+            data,
+        }
+    }
+    /// Take the data out of this slice, releasing its memory if it is not shared
+    fn take_data(&mut self) -> EcoVec<T> {
+        if self.data.is_unique() {
+            memory::release(alloc_size(&self.data));
+        }
+        take(&mut self.data)
+    }
+    /// Get the address and size in bytes of the allocation this slice is in
+    pub(crate) fn allocation(&self) -> (usize, usize) {
+        (self.data.as_ptr() as usize, alloc_size(&self.data))
+    }
+}
+
+fn alloc_size<T>(data: &EcoVec<T>) -> usize {
+    data.capacity() * size_of::<T>()
+}
+
+impl<T> Drop for CowSlice<T> {
+    fn drop(&mut self) {
+        if self.data.is_unique() {
+            memory::release(alloc_size(&self.data));
+        }
+    }
 }
 
 impl<T: Clone> CowSlice<T> {
@@ -58,13 +93,24 @@ impl<T: Clone> CowSlice<T> {
             end,
         }
     }
+    /// Modify the data in place if it is not shared, or a copy of it if it is
+    ///
+    /// Growth of the data is only tracked once `f` returns, so callers that grow it
+    /// by more than a little should [`memory::reserve`] the new space first.
     pub fn modify<F, R>(&mut self, f: F) -> R
     where
         F: FnOnce(&mut EcoVec<T>) -> R,
     {
         if self.data.is_unique() && self.start == 0 && self.end == self.data.len() as u32 {
+            let before = alloc_size(&self.data);
             let res = f(&mut self.data);
+            let after = alloc_size(&self.data);
             self.end = self.data.len() as u32;
+            if after > before {
+                memory::track(after - before);
+            } else {
+                memory::release(before - after);
+            }
             res
         } else {
             let mut vec = EcoVec::from(&**self);
@@ -137,18 +183,14 @@ fn cow_slice_deref_mut() {
 
 impl<T: Clone> From<Vec<T>> for CowSlice<T> {
     fn from(vec: Vec<T>) -> Self {
-        Self {
-            start: 0,
-            end: vec.len() as u32,
-            data: vec.into(),
-        }
+        Self::tracked(vec.into())
     }
 }
 
 impl<T: Clone> From<CowSlice<T>> for Vec<T> {
     fn from(mut slice: CowSlice<T>) -> Self {
         if slice.data.is_unique() && slice.start == 0 && slice.end == slice.data.len() as u32 {
-            slice.data.into_iter().collect()
+            slice.take_data().into_iter().collect()
         } else {
             slice.to_vec()
         }
@@ -157,31 +199,19 @@ impl<T: Clone> From<CowSlice<T>> for Vec<T> {
 
 impl<T: Clone> From<EcoVec<T>> for CowSlice<T> {
     fn from(data: EcoVec<T>) -> Self {
-        Self {
-            start: 0,
-            end: data.len() as u32,
-            data,
-        }
+        Self::tracked(data)
     }
 }
 
 impl<'a, T: Clone> From<&'a [T]> for CowSlice<T> {
     fn from(slice: &'a [T]) -> Self {
-        Self {
-            start: 0,
-            end: slice.len() as u32,
-            data: slice.into(),
-        }
+        Self::tracked(slice.into())
     }
 }
 
 impl<T: Clone, const N: usize> From<[T; N]> for CowSlice<T> {
     fn from(array: [T; N]) -> Self {
-        Self {
-            start: 0,
-            end: N as u32,
-            data: array.into(),
-        }
+        Self::tracked(array.into())
     }
 }
 
@@ -251,8 +281,8 @@ impl<T: Clone> IntoIterator for CowSlice<T> {
     type Item = T;
     type IntoIter = Take<Skip<<EcoVec<T> as IntoIterator>::IntoIter>>;
     #[allow(clippy::unnecessary_to_owned)]
-    fn into_iter(self) -> Self::IntoIter {
-        self.data
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.take_data()
             .into_iter()
             .skip(self.start as usize)
             .take((self.end - self.start) as usize)
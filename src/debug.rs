@@ -0,0 +1,363 @@
+//! The step debugger
+//!
+//! A [`Debugger`] can be attached to a runtime with [`Uiua::with_debugger`].
+//! Before each instruction is executed, the debugger checks whether it should pause.
+//! When it does, its handler is called with the paused runtime, which can be inspected
+//! with [`Uiua::stack`], [`Uiua::inline_stack`], [`Uiua::under_stack`], and [`Uiua::call_stack`].
+
+use std::{collections::BTreeSet, fmt, path::Path};
+
+use crate::{
+    function::{FunctionId, Instr},
+    lex::{CodeSpan, Loc, Span},
+    Ident, Uiua, UiuaResult,
+};
+
+/// A place to pause execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Pause when execution reaches code in this span
+    Span(CodeSpan),
+    /// Pause when the function bound to this name is called
+    Binding(Ident),
+}
+
+impl Breakpoint {
+    /// Create a breakpoint at a line, or at a single column of a line, of some input
+    ///
+    /// Returns `None` if the input does not have the line or column
+    pub fn at_line(
+        input: &str,
+        path: Option<&Path>,
+        line: usize,
+        col: Option<usize>,
+    ) -> Option<Self> {
+        let mut char_pos = 0;
+        let mut byte_pos = 0;
+        for (i, text) in input.split('\n').enumerate() {
+            if i + 1 == line {
+                let line_len = text.chars().count();
+                let (start_col, end_col) = match col {
+                    Some(col) if (1..=line_len).contains(&col) => (col, col + 1),
+                    Some(_) => return None,
+                    None => (1, line_len + 1),
+                };
+                let loc = |col: usize| {
+                    let byte_offset: usize = text.chars().take(col - 1).map(char::len_utf8).sum();
+                    Loc {
+                        char_pos: char_pos + col - 1,
+                        byte_pos: byte_pos + byte_offset,
+                        line,
+                        col,
+                    }
+                };
+                return Some(Breakpoint::Span(CodeSpan {
+                    start: loc(start_col),
+                    end: loc(end_col),
+                    path: path.map(Into::into),
+                    input: input.into(),
+                }));
+            }
+            char_pos += text.chars().count() + 1;
+            byte_pos += text.len() + 1;
+        }
+        None
+    }
+    fn matches_span(&self, span: &CodeSpan) -> bool {
+        let Breakpoint::Span(bp) = self else {
+            return false;
+        };
+        if bp.path.is_some() && bp.path != span.path {
+            return false;
+        }
+        let start = |span: &CodeSpan| (span.start.line, span.start.col);
+        let end = |span: &CodeSpan| (span.end.line, span.end.col);
+        start(span) < end(bp) && start(bp) < end(span)
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Span(span) => write!(f, "{span}"),
+            Breakpoint::Binding(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The breakpoints of a [`Debugger`]
+///
+/// Each breakpoint gets an id when it is added. Ids start at 1 and are never reused,
+/// so removing a breakpoint does not change the ids of the others.
+#[derive(Debug, Clone)]
+pub struct Breakpoints {
+    list: Vec<(usize, Breakpoint)>,
+    next_id: usize,
+}
+
+impl Default for Breakpoints {
+    fn default() -> Self {
+        Self {
+            list: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl Breakpoints {
+    /// Add a breakpoint and get its id
+    pub fn add(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.list.push((id, breakpoint));
+        id
+    }
+    /// Remove the breakpoint with an id
+    pub fn remove(&mut self, id: usize) -> Option<Breakpoint> {
+        let i = self.list.iter().position(|(bp_id, _)| *bp_id == id)?;
+        Some(self.list.remove(i).1)
+    }
+    /// Get the breakpoint with an id
+    pub fn get(&self, id: usize) -> Option<&Breakpoint> {
+        (self.list.iter())
+            .find(|(bp_id, _)| *bp_id == id)
+            .map(|(_, bp)| bp)
+    }
+    /// Iterate over the ids and breakpoints in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.list.iter().map(|(id, bp)| (*id, bp))
+    }
+    /// Get the number of breakpoints
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+    /// Check whether there are no breakpoints
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+}
+
+/// What the debugger should do after pausing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    /// Run until the next breakpoint
+    Continue,
+    /// Execute a single instruction, stepping into any function calls
+    StepInstr,
+    /// Execute a single word, stepping over any function calls
+    StepWord,
+    /// Run until the current function returns
+    StepOut,
+    /// Stop execution with an error
+    Abort,
+}
+
+/// The reason execution was paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    /// A step finished
+    Step,
+    /// The breakpoint with this id was hit
+    Breakpoint(usize),
+}
+
+/// A frame in the call stack of a paused runtime
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    /// The function being executed
+    pub id: FunctionId,
+    /// The span at which the function was called
+    pub call_span: Span,
+    /// The index of the next instruction to execute
+    pub pc: usize,
+    /// The next instruction to execute
+    pub instr: Option<Instr>,
+    /// The span of the next instruction to execute, if it has one
+    pub span: Option<Span>,
+}
+
+type DebugHandler = dyn FnMut(&Uiua, PauseReason, &mut Breakpoints) -> DebugCommand + Send;
+
+/// A step debugger
+pub struct Debugger {
+    breakpoints: Breakpoints,
+    stepping: Stepping,
+    /// Ids of span breakpoints that matched the last instruction with a span
+    in_breakpoints: BTreeSet<usize>,
+    handler: Box<DebugHandler>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stepping {
+    Continue,
+    Instr,
+    Word(usize),
+    Out(usize),
+}
+
+impl Debugger {
+    /// Create a new debugger
+    ///
+    /// The handler is called whenever execution pauses. It can inspect the runtime,
+    /// change the breakpoints, and decides how execution continues.
+    pub fn new(
+        handler: impl FnMut(&Uiua, PauseReason, &mut Breakpoints) -> DebugCommand + Send + 'static,
+    ) -> Self {
+        Self {
+            breakpoints: Breakpoints::default(),
+            stepping: Stepping::Continue,
+            in_breakpoints: BTreeSet::new(),
+            handler: Box::new(handler),
+        }
+    }
+    /// Add a breakpoint
+    pub fn with_breakpoint(mut self, breakpoint: Breakpoint) -> Self {
+        self.breakpoints.add(breakpoint);
+        self
+    }
+    /// Pause before the first instruction is executed
+    pub fn pause_at_start(mut self) -> Self {
+        self.stepping = Stepping::Instr;
+        self
+    }
+    /// Get the breakpoints
+    pub fn breakpoints(&self) -> &Breakpoints {
+        &self.breakpoints
+    }
+    /// Check whether execution should pause at an instruction
+    ///
+    /// `depth` is the depth of the call stack, `span` is the span of the instruction,
+    /// and `entered` is the id of the function if the instruction is its first.
+    pub(crate) fn check(
+        &mut self,
+        depth: usize,
+        span: Option<&Span>,
+        entered: Option<&FunctionId>,
+    ) -> Option<PauseReason> {
+        let mut hit = None;
+        if let Some(FunctionId::Named(name)) = entered {
+            hit = (self.breakpoints.iter())
+                .find(|(_, bp)| matches!(bp, Breakpoint::Binding(bp) if bp == name))
+                .map(|(id, _)| id);
+        }
+        if let Some(Span::Code(span)) = span {
+            let matching: BTreeSet<usize> = (self.breakpoints.iter())
+                .filter(|(_, bp)| bp.matches_span(span))
+                .map(|(id, _)| id)
+                .collect();
+            hit = hit.or_else(|| matching.difference(&self.in_breakpoints).next().copied());
+            self.in_breakpoints = matching;
+        }
+        if let Some(id) = hit {
+            return Some(PauseReason::Breakpoint(id));
+        }
+        match self.stepping {
+            Stepping::Continue => None,
+            Stepping::Instr => Some(PauseReason::Step),
+            Stepping::Word(d) => (depth <= d).then_some(PauseReason::Step),
+            Stepping::Out(d) => (depth < d).then_some(PauseReason::Step),
+        }
+    }
+    /// Call the handler and set up the next step
+    pub(crate) fn pause(&mut self, env: &Uiua, reason: PauseReason, depth: usize) -> UiuaResult {
+        self.stepping = match (self.handler)(env, reason, &mut self.breakpoints) {
+            DebugCommand::Continue => Stepping::Continue,
+            DebugCommand::StepInstr => Stepping::Instr,
+            DebugCommand::StepWord => Stepping::Word(depth),
+            DebugCommand::StepOut => Stepping::Out(depth),
+            DebugCommand::Abort => return Err(env.error("Execution aborted by the debugger")),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::value::Value;
+
+    #[test]
+    fn breakpoints() {
+        let pauses = Arc::new(Mutex::new(Vec::new()));
+        let debugger = Debugger::new({
+            let pauses = pauses.clone();
+            move |env, reason, _| {
+                let frame = env.call_stack().pop().unwrap();
+                pauses
+                    .lock()
+                    .unwrap()
+                    .push((reason, frame.id, env.stack().len()));
+                DebugCommand::Continue
+            }
+        })
+        .with_breakpoint(Breakpoint::Binding("F".into()));
+        let mut env = Uiua::with_native_sys().with_debugger(debugger);
+        env.load_str("F ← +1\nF F 5").unwrap();
+        let pauses = pauses.lock().unwrap();
+        assert_eq!(pauses.len(), 2);
+        for (reason, id, height) in pauses.iter() {
+            assert_eq!(*reason, PauseReason::Breakpoint(1));
+            assert_eq!(*id, FunctionId::Named("F".into()));
+            assert_eq!(*height, 1);
+        }
+
+        let input = "1\n+ 2 3";
+        let mut steps = 0;
+        let debugger = Debugger::new(move |env, reason, _| {
+            if reason == PauseReason::Breakpoint(1) {
+                assert_eq!(env.stack().len(), 3);
+            }
+            steps += 1;
+            assert!(steps <= 1);
+            DebugCommand::Continue
+        })
+        .with_breakpoint(Breakpoint::at_line(input, None, 2, None).unwrap());
+        let mut env = Uiua::with_native_sys().with_debugger(debugger);
+        env.load_str(input).unwrap();
+    }
+
+    #[test]
+    fn breakpoint_ids() {
+        let input = "⇡1\n⇡2\n⇡3";
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let debugger = Debugger::new({
+            let hits = hits.clone();
+            move |_, reason, breakpoints| {
+                if let PauseReason::Breakpoint(id) = reason {
+                    hits.lock().unwrap().push(id);
+                }
+                // Removing a breakpoint does not change the ids of the others
+                breakpoints.remove(1);
+                DebugCommand::Continue
+            }
+        })
+        .with_breakpoint(Breakpoint::at_line(input, None, 1, None).unwrap())
+        .with_breakpoint(Breakpoint::at_line(input, None, 2, None).unwrap())
+        .with_breakpoint(Breakpoint::at_line(input, None, 3, None).unwrap());
+        let mut env = Uiua::with_native_sys().with_debugger(debugger);
+        env.load_str(input).unwrap();
+        assert_eq!(*hits.lock().unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn breakpoint_on_foldable_line() {
+        let input = "1\n+ 2 3";
+        let heights = Arc::new(Mutex::new(Vec::new()));
+        let debugger = Debugger::new({
+            let heights = heights.clone();
+            move |env, reason, _| {
+                assert_eq!(reason, PauseReason::Breakpoint(1));
+                heights.lock().unwrap().push(env.stack().len());
+                DebugCommand::Continue
+            }
+        })
+        .with_breakpoint(Breakpoint::at_line(input, None, 2, None).unwrap());
+        let mut env = Uiua::with_native_sys().with_debugger(debugger);
+        env.load_str(input).unwrap();
+        // The addition is run rather than folded into a constant
+        assert_eq!(*heights.lock().unwrap(), [3]);
+        assert_eq!(env.take_stack(), [Value::from(1.0), Value::from(5.0)]);
+    }
+}
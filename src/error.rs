@@ -30,6 +30,7 @@ pub enum UiuaError {
     Throw(Box<Value>, Span),
     Break(usize, Span),
     Timeout(Span),
+    MemoryLimit(Span),
     Fill(Box<Self>),
 }
 
@@ -76,6 +77,7 @@ impl fmt::Display for UiuaError {
             UiuaError::Throw(value, span) => write!(f, "{span}: {value}"),
             UiuaError::Break(_, span) => write!(f, "{span}: break outside of loop"),
             UiuaError::Timeout(_) => write!(f, "Maximum execution time exceeded"),
+            UiuaError::MemoryLimit(_) => write!(f, "Maximum memory usage exceeded"),
             UiuaError::Fill(error) => error.fmt(f),
         }
     }
@@ -213,6 +215,11 @@ impl UiuaError {
                 kind,
                 color,
             ),
+            UiuaError::MemoryLimit(span) => report(
+                [("Maximum memory usage exceeded", span.clone())],
+                kind,
+                color,
+            ),
             UiuaError::Fill(error) => error.show(color),
             UiuaError::Load(..) | UiuaError::Format(..) => self.to_string(),
         }
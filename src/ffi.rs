@@ -0,0 +1,341 @@
+//! Calling functions in C shared libraries with [`&ffi`](crate::SysOp::Ffi)
+//!
+//! Functions are declared with a C-like signature, like `double dot(const double*, const double*, int)`.
+//! Supported types are `int`, `long`, `double`, pointers to `double` or `unsigned char`,
+//! and `void` as a return type. Pointers to non-`const` data are out-parameters.
+//!
+//! Functions are called through libffi, using the platform's C calling convention for the
+//! declared types. Variadic functions like `printf` cannot be declared, and calling one
+//! as if it were not variadic is undefined behavior.
+
+use std::{fmt, str::FromStr};
+
+/// A C type that can be used in an FFI declaration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfiType {
+    Void,
+    Int,
+    Long,
+    Double,
+    DoublePtr { mutable: bool },
+    BytePtr { mutable: bool },
+}
+
+impl FfiType {
+    /// Whether the function can write to this argument
+    pub fn is_out_param(&self) -> bool {
+        matches!(
+            self,
+            FfiType::DoublePtr { mutable: true } | FfiType::BytePtr { mutable: true }
+        )
+    }
+}
+
+impl fmt::Display for FfiType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let constness = |mutable: bool| if mutable { "" } else { "const " };
+        match self {
+            FfiType::Void => write!(f, "void"),
+            FfiType::Int => write!(f, "int"),
+            FfiType::Long => write!(f, "long"),
+            FfiType::Double => write!(f, "double"),
+            FfiType::DoublePtr { mutable } => write!(f, "{}double*", constness(*mutable)),
+            FfiType::BytePtr { mutable } => write!(f, "{}unsigned char*", constness(*mutable)),
+        }
+    }
+}
+
+impl FromStr for FfiType {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let spaced = s.replace('*', " * ");
+        let mut words: Vec<&str> = spaced.split_whitespace().collect();
+        let mutable = !words.contains(&"const");
+        words.retain(|&word| word != "const");
+        let pointer = words.last() == Some(&"*");
+        if pointer {
+            words.pop();
+        }
+        let base = words.join(" ");
+        Ok(match (base.as_str(), pointer) {
+            ("void", false) => FfiType::Void,
+            ("int" | "int32_t", false) => FfiType::Int,
+            ("long" | "long int" | "int64_t" | "size_t", false) => FfiType::Long,
+            ("double", false) => FfiType::Double,
+            ("double", true) => FfiType::DoublePtr { mutable },
+            ("char" | "unsigned char" | "uint8_t", true) => FfiType::BytePtr { mutable },
+            _ => return Err(format!("Unsupported C type: {}", s.trim())),
+        })
+    }
+}
+
+/// A declaration of a C function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfiDecl {
+    pub name: String,
+    pub ret: FfiType,
+    pub args: Vec<FfiType>,
+}
+
+impl FromStr for FfiDecl {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid C function declaration: {}", s.trim());
+        let s = s.trim().trim_end_matches(';');
+        let (head, params) = s.split_once('(').ok_or_else(invalid)?;
+        let params = params.strip_suffix(')').ok_or_else(invalid)?;
+        let head = head.trim_end();
+        let name_start = head
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        let (ret, name) = head.split_at(name_start);
+        if name.is_empty() || ret.trim().is_empty() {
+            return Err(invalid());
+        }
+        let mut args = Vec::new();
+        if !matches!(params.trim(), "" | "void") {
+            for param in params.split(',') {
+                args.push(without_param_name(param).parse::<FfiType>()?);
+            }
+        }
+        if args.contains(&FfiType::Void) {
+            return Err(invalid());
+        }
+        let ret: FfiType = ret.parse()?;
+        if matches!(ret, FfiType::DoublePtr { .. } | FfiType::BytePtr { .. }) {
+            return Err(format!("Unsupported return type: {ret}"));
+        }
+        Ok(FfiDecl {
+            name: name.into(),
+            ret,
+            args,
+        })
+    }
+}
+
+/// Remove the name from a parameter declaration, if it has one
+fn without_param_name(param: &str) -> &str {
+    let param = param.trim();
+    let Some((ty, name)) = param.rsplit_once(|c: char| c.is_whitespace() || c == '*') else {
+        return param;
+    };
+    let is_type_word = |word: &str| {
+        ["void", "int", "long", "double", "char", "const"].contains(&word) || word.ends_with("_t")
+    };
+    if ty.trim().is_empty() || is_type_word(name) {
+        param
+    } else {
+        &param[..ty.len() + 1]
+    }
+}
+
+#[cfg(feature = "ffi")]
+pub(crate) use call::ffi_call;
+
+#[cfg(feature = "ffi")]
+mod call {
+    use std::ffi::c_void;
+
+    use libffi::{
+        low::ffi_sarg,
+        middle::{arg, Arg, Cif, CodePtr, Type},
+    };
+    use libloading::Library;
+
+    use super::*;
+    use crate::{array::Array, convert::unboxed, function::Function, value::Value, FromValue};
+
+    enum Prepared {
+        Int(i32),
+        Long(i64),
+        Double(f64),
+        Doubles(Array<f64>, bool),
+        Bytes(Array<u8>, bool),
+    }
+
+    fn ffi_type(ty: FfiType) -> Type {
+        match ty {
+            FfiType::Void => Type::void(),
+            FfiType::Int => Type::i32(),
+            FfiType::Long => Type::i64(),
+            FfiType::Double => Type::f64(),
+            FfiType::DoublePtr { .. } | FfiType::BytePtr { .. } => Type::pointer(),
+        }
+    }
+
+    /// Call a function in a loaded library
+    ///
+    /// The function's result is returned, unless it has out-parameters.
+    /// In that case, a list of the boxed result (if it is not `void`) and out-parameters is returned.
+    pub(crate) fn ffi_call(lib: &Library, decl: &FfiDecl, args: &[Value]) -> Result<Value, String> {
+        if args.len() != decl.args.len() {
+            return Err(format!(
+                "{} takes {} argument{}, but {} were given",
+                decl.name,
+                decl.args.len(),
+                if decl.args.len() == 1 { "" } else { "s" },
+                args.len()
+            ));
+        }
+        let mut prepared = Vec::with_capacity(args.len());
+        for (i, (ty, arg)) in decl.args.iter().zip(args).enumerate() {
+            let arg = unboxed(arg.clone());
+            let context = |e: String| format!("Argument {} of {}: {e}", i + 1, decl.name);
+            prepared.push(match *ty {
+                FfiType::Int => Prepared::Int(i32::from_value(arg).map_err(context)?),
+                FfiType::Long => {
+                    let n = f64::from_value(arg).map_err(context)?;
+                    if n.fract() != 0.0 || !(-(2f64.powi(63))..2f64.powi(63)).contains(&n) {
+                        return Err(context(format!(
+                            "Expected a 64-bit integer, but the value is {n}"
+                        )));
+                    }
+                    Prepared::Long(n as i64)
+                }
+                FfiType::Double => Prepared::Double(f64::from_value(arg).map_err(context)?),
+                FfiType::DoublePtr { mutable } => {
+                    Prepared::Doubles(Array::<f64>::from_value(arg).map_err(context)?, mutable)
+                }
+                FfiType::BytePtr { mutable } => {
+                    Prepared::Bytes(bytes_from_value(arg).map_err(context)?, mutable)
+                }
+                FfiType::Void => unreachable!("void arguments are rejected when parsing"),
+            });
+        }
+        // Pointers into the arrays stay valid because the arrays are not moved until after the call
+        let pointers: Vec<*mut c_void> = (prepared.iter_mut())
+            .map(|arg| match arg {
+                Prepared::Doubles(arr, true) => arr.data.as_mut_ptr() as *mut c_void,
+                Prepared::Doubles(arr, false) => arr.data.as_ptr() as *mut c_void,
+                Prepared::Bytes(arr, true) => arr.data.as_mut_ptr() as *mut c_void,
+                Prepared::Bytes(arr, false) => arr.data.as_ptr() as *mut c_void,
+                _ => std::ptr::null_mut(),
+            })
+            .collect();
+        let call_args: Vec<Arg> = (prepared.iter().zip(&pointers))
+            .map(|(prepared, pointer)| match prepared {
+                Prepared::Int(n) => arg(n),
+                Prepared::Long(n) => arg(n),
+                Prepared::Double(x) => arg(x),
+                Prepared::Doubles(..) | Prepared::Bytes(..) => arg(pointer),
+            })
+            .collect();
+        let cif = Cif::new(decl.args.iter().copied().map(ffi_type), ffi_type(decl.ret));
+        // Safety: the library's author is trusted to have declared the function correctly
+        let ret = unsafe {
+            let f = *lib
+                .get::<*const c_void>(decl.name.as_bytes())
+                .map_err(|e| e.to_string())?;
+            let f = CodePtr::from_ptr(f);
+            match decl.ret {
+                FfiType::Void => {
+                    cif.call::<()>(f, &call_args);
+                    None
+                }
+                // libffi widens small integer results to a whole `ffi_sarg`
+                FfiType::Int => Some(Value::from(
+                    cif.call::<ffi_sarg>(f, &call_args) as i32 as f64
+                )),
+                FfiType::Long => Some(Value::from(cif.call::<i64>(f, &call_args) as f64)),
+                FfiType::Double => Some(Value::from(cif.call::<f64>(f, &call_args))),
+                FfiType::DoublePtr { .. } | FfiType::BytePtr { .. } => {
+                    unreachable!("pointer return types are rejected when parsing")
+                }
+            }
+        };
+        let mut outputs: Vec<Value> = ret.into_iter().collect();
+        let has_out_params = decl.args.iter().any(FfiType::is_out_param);
+        if !has_out_params {
+            return Ok(outputs
+                .pop()
+                .unwrap_or_else(|| Array::<f64>::from(Vec::new()).into()));
+        }
+        for arg in prepared {
+            match arg {
+                Prepared::Doubles(arr, true) => outputs.push(arr.into()),
+                Prepared::Bytes(arr, true) => outputs.push(arr.into()),
+                _ => {}
+            }
+        }
+        Ok(outputs
+            .into_iter()
+            .map(|val| std::sync::Arc::new(Function::constant(val)))
+            .collect())
+    }
+
+    /// Convert a value to bytes, allowing numbers that are all bytes
+    fn bytes_from_value(value: Value) -> Result<Array<u8>, String> {
+        let nums = match value {
+            Value::Num(nums) => nums,
+            value => return Array::<u8>::from_value(value),
+        };
+        if let Some(n) =
+            (nums.data.iter()).find(|n| !(n.fract() == 0.0 && (0.0..=255.0).contains(*n)))
+        {
+            return Err(format!("Expected a byte array, but the value contains {n}"));
+        }
+        Ok(nums.convert_with(|n| n as u8))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ffi_decl() {
+        let decl: FfiDecl = "void scale(double *xs, int n, double k);".parse().unwrap();
+        assert_eq!(decl.name, "scale");
+        assert_eq!(decl.ret, FfiType::Void);
+        assert_eq!(
+            decl.args,
+            [
+                FfiType::DoublePtr { mutable: true },
+                FfiType::Int,
+                FfiType::Double
+            ]
+        );
+        let decl: FfiDecl = "long count(const unsigned char*, long)".parse().unwrap();
+        assert_eq!(decl.ret, FfiType::Long);
+        assert_eq!(
+            decl.args,
+            [FfiType::BytePtr { mutable: false }, FfiType::Long]
+        );
+        let decl: FfiDecl = "double seed(void)".parse().unwrap();
+        assert!(decl.args.is_empty());
+        assert!("double* bad(int)".parse::<FfiDecl>().is_err());
+        assert!("int bad(float)".parse::<FfiDecl>().is_err());
+        assert!("int bad".parse::<FfiDecl>().is_err());
+    }
+
+    #[cfg(all(feature = "ffi", target_os = "linux"))]
+    #[test]
+    fn ffi_call() {
+        use crate::Uiua;
+        let mut env = Uiua::with_native_sys();
+        env.load_str(
+            "&ffi \"libm.so.6\" \"double cos(double)\" {0}\n\
+             &ffi \"libm.so.6\" \"double fmax(double x, double y)\" {2 5}\n\
+             &ffi \"libc.so.6\" \"int abs(int)\" {¯3}\n\
+             &ffi \"libc.so.6\" \"long labs(long)\" {¯5000000000}\n\
+             &ffi \"libc.so.6\" \"void memset(unsigned char*, int, long)\" {[1 2 3] 7 2}",
+        )
+        .unwrap();
+        let stack = env.take_stack();
+        assert_eq!(stack[0], 1.0.into());
+        assert_eq!(stack[1], 5.0.into());
+        assert_eq!(stack[2], 3.0.into());
+        assert_eq!(stack[3], 5e9.into());
+        let out = stack[4].as_func_array().unwrap().data[0]
+            .as_constant()
+            .cloned();
+        let expected = crate::array::Array::<u8>::from(vec![7, 7, 3]);
+        assert_eq!(out, Some(expected.into()));
+        assert!(env
+            .load_str("&ffi \"libm.so.6\" \"double cos(double)\" {1 2}")
+            .is_err());
+        assert!(env
+            .load_str("&ffi \"libc.so.6\" \"long labs(long)\" {1e19}")
+            .is_err());
+    }
+}
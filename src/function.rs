@@ -6,9 +6,11 @@ use std::{
     sync::Arc,
 };
 
+use once_cell::sync::OnceCell;
+
 use crate::{
-    check::instrs_signature, lex::CodeSpan, primitive::Primitive, value::Value, Ident, Uiua,
-    UiuaResult,
+    algorithm::pervade::Fused, check::instrs_signature, lex::CodeSpan, primitive::Primitive,
+    value::Value, Ident, Uiua, UiuaResult,
 };
 
 #[derive(Clone)]
@@ -44,6 +46,7 @@ pub enum Instr {
         span: usize,
         kind: TempKind,
     },
+    Fused(Arc<Fused>),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -75,6 +78,7 @@ impl PartialEq for Instr {
                 },
             ) => ao == bo && ac == bc,
             (Self::DropTemp { count: a, .. }, Self::DropTemp { count: b, .. }) => a == b,
+            (Self::Fused(a), Self::Fused(b)) => a.instrs == b.instrs,
             _ => false,
         }
     }
@@ -123,6 +127,7 @@ impl Hash for Instr {
                 count.hash(state);
             }
             Instr::DropTemp { count, .. } => count.hash(state),
+            Instr::Fused(fused) => fused.instrs.hash(state),
         }
     }
 }
@@ -137,6 +142,19 @@ impl Instr {
             _ => None,
         }
     }
+    /// Get the index of the instruction's span, if it has one
+    pub fn span(&self) -> Option<usize> {
+        match self {
+            Instr::EndArray { span, .. }
+            | Instr::Prim(_, span)
+            | Instr::Call(span)
+            | Instr::PushTemp { span, .. }
+            | Instr::PopTemp { span, .. }
+            | Instr::CopyTemp { span, .. }
+            | Instr::DropTemp { span, .. } => Some(*span),
+            Instr::Push(_) | Instr::BeginArray | Instr::Dynamic(_) | Instr::Fused(_) => None,
+        }
+    }
     pub fn is_temp(&self) -> bool {
         matches!(
             self,
@@ -173,6 +191,13 @@ impl fmt::Display for Instr {
                 ..
             } => write!(f, "<copy {kind:?} {offset}/{count}>"),
             Instr::DropTemp { count, kind, .. } => write!(f, "<drop {kind:?} {count}>"),
+            Instr::Fused(fused) => {
+                write!(f, "<fused")?;
+                for instr in &fused.instrs {
+                    write!(f, " {instr}")?;
+                }
+                write!(f, ">")
+            }
         }
     }
 }
@@ -182,6 +207,8 @@ pub struct Function {
     pub id: FunctionId,
     pub instrs: Vec<Instr>,
     signature: Signature,
+    /// Whether the function is pure, computed the first time it is needed
+    pure: OnceCell<bool>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -229,6 +256,16 @@ impl fmt::Display for Signature {
     }
 }
 
+/// A signature inferred for a binding or inline function
+#[cfg(feature = "lsp")]
+#[derive(Debug, Clone)]
+pub(crate) struct InferredSignature {
+    /// The span of the binding's name or the function
+    pub span: CodeSpan,
+    pub inferred: Signature,
+    pub declared: Option<Signature>,
+}
+
 #[derive(Clone)]
 pub struct DynamicFunction {
     pub id: u64,
@@ -268,6 +305,17 @@ impl Hash for DynamicFunction {
     }
 }
 
+/// Information about a Rust function bound with [`Uiua::bind_native`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeBinding {
+    /// The name of the binding
+    pub name: Ident,
+    /// The signature of the function
+    pub signature: Signature,
+    /// Documentation for the function
+    pub doc: String,
+}
+
 impl PartialEq for Function {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id && self.instrs == other.instrs
@@ -325,6 +373,7 @@ impl Function {
             id,
             instrs,
             signature,
+            pure: OnceCell::new(),
         }
     }
     pub fn new_inferred(id: FunctionId, instrs: impl Into<Vec<Instr>>) -> Result<Self, String> {
@@ -334,6 +383,7 @@ impl Function {
             id,
             signature,
             instrs,
+            pure: OnceCell::new(),
         })
     }
     pub fn into_inner(f: Arc<Self>) -> Self {
@@ -399,6 +449,7 @@ impl Function {
         }
     }
     pub fn as_constant_mut(&mut self) -> Option<&mut Value> {
+        self.pure = OnceCell::new();
         match self.instrs.as_mut_slice() {
             [Instr::Push(val)] => Some(val),
             _ => None,
@@ -414,6 +465,30 @@ impl Function {
             },
         }
     }
+    /// Check if calling the function can only affect the stack
+    ///
+    /// This is conservative. Functions that use system functions, [`Primitive::Rand`],
+    /// threads, tracing, or functions whose bodies are not known are not pure.
+    ///
+    /// The result is cached, because loops check it on every call.
+    pub(crate) fn is_pure(&self) -> bool {
+        use Primitive::*;
+        *self.pure.get_or_init(|| {
+            self.instrs
+                .iter()
+                .enumerate()
+                .all(|(i, instr)| match instr {
+                    Instr::Push(val) => val.is_pure(),
+                    Instr::Prim(
+                        Sys(_) | Rand | Tag | Spawn | Wait | Break | Call | Trace | InvTrace | Dump,
+                        _,
+                    ) => false,
+                    Instr::Call(_) => i > 0 && matches!(self.instrs[i - 1], Instr::Push(_)),
+                    Instr::Dynamic(_) => false,
+                    _ => true,
+                })
+        })
+    }
     pub fn compose(a: Arc<Self>, b: Arc<Self>) -> Self {
         let id = a.id.clone().compose(b.id.clone());
         let sig = a.signature.compose(b.signature);
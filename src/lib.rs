@@ -12,24 +12,37 @@ pub mod array;
 pub mod ast;
 mod check;
 mod compile;
+pub mod convert;
 mod cowslice;
+pub mod debug;
 mod error;
+mod ffi;
 pub mod format;
 pub mod function;
 mod grid_fmt;
 pub mod lex;
 pub mod lsp;
+mod memory;
+mod optimize;
 pub mod parse;
 pub mod primitive;
 #[doc(hidden)]
 pub mod profile;
 pub mod run;
+mod sandbox;
 mod sys;
 pub mod value;
 
 use std::sync::Arc;
 
-pub use {error::*, run::Uiua, sys::*};
+pub use {
+    convert::{FromValue, IntoValue},
+    error::*,
+    ffi::{FfiDecl, FfiType},
+    run::Uiua,
+    sandbox::SandboxSys,
+    sys::*,
+};
 
 pub type Ident = Arc<str>;
 
@@ -98,11 +98,18 @@ fn words_spans(words: &[Sp<Word>]) -> Vec<Sp<SpanKind>> {
 }
 
 #[cfg(feature = "lsp")]
-pub use server::run_server;
+pub use server::{run_server, run_server_with_natives};
 
 #[cfg(feature = "lsp")]
 mod server {
-    use std::{collections::BTreeMap, sync::Arc};
+    use std::{
+        any::Any,
+        collections::{BTreeMap, HashMap},
+        fs,
+        path::Path,
+        sync::Arc,
+        time::Duration,
+    };
 
     use dashmap::DashMap;
     use tower_lsp::{jsonrpc::Result, lsp_types::*, *};
@@ -111,15 +118,26 @@ mod server {
 
     use crate::{
         format::{format_str, FormatConfig /*, FormatConfigSource*/},
-        lex::Loc,
-        primitive::PrimDocFragment,
-        Ident, Uiua,
+        function::{InferredSignature, NativeBinding, Signature},
+        lex::{is_ident_char, Loc, Span},
+        primitive::{PrimDoc, PrimDocFragment, PrimDocLine},
+        run::RunMode,
+        DiagnosticKind, Ident, NativeSys, SysBackend, SysOp, Uiua, UiuaError,
     };
 
     pub struct LspDoc {
         pub input: String,
         pub spans: Vec<Sp<SpanKind>>,
         pub bindings: BindingsInfo,
+        pub imports: Vec<ImportInfo>,
+        pub uses: Vec<UseInfo>,
+        pub exports: HashMap<Ident, Arc<BindingInfo>>,
+        /// The start and end of each scope
+        pub scopes: Vec<(Loc, Loc)>,
+        /// Hints of inferred signatures, which are set when the document is checked
+        pub hints: Vec<InlayHint>,
+        /// The signatures of function bindings, which are set when the document is checked
+        pub signatures: Vec<(CodeSpan, Signature)>,
     }
 
     type BindingsInfo = BTreeMap<Sp<Ident>, Arc<BindingInfo>>;
@@ -128,76 +146,269 @@ mod server {
         fn new(input: String) -> Self {
             let (items, _, _) = parse(&input, None);
             let spans = items_spans(&items);
-            let bindings = bindings_info(&items);
+            let mut builder = BindingsBuilder {
+                scopes: vec![Scope::default()],
+                ..Default::default()
+            };
+            builder.items(&items);
             Self {
                 input,
                 spans,
-                bindings,
+                bindings: builder.bindings,
+                imports: builder.imports,
+                uses: builder.uses,
+                exports: builder.scopes.pop().unwrap().names,
+                scopes: builder.scope_spans,
+                hints: Vec::new(),
+                signatures: Vec::new(),
+            }
+        }
+        /// Get the binding referenced at a position
+        fn binding_at(&self, line: usize, col: usize) -> Option<(&Sp<Ident>, &Arc<BindingInfo>)> {
+            (self.bindings.iter()).find(|(ident, _)| ident.span.contains_line_col(line, col))
+        }
+        /// Get the bindings that are visible at a position
+        fn bindings_in_scope(&self, line: usize, col: usize) -> BTreeMap<Ident, Arc<BindingInfo>> {
+            let mut visible = BTreeMap::new();
+            let mut defs: Vec<_> = (self.bindings.iter())
+                .filter(|(ident, info)| same_span(&ident.span, &info.span))
+                .collect();
+            defs.sort_by_key(|(ident, _)| ident.span.start);
+            for (ident, info) in defs {
+                let start = ident.span.start;
+                let defined = (start.line, start.col) < (line, col);
+                let in_scope = match info.scope {
+                    Some(i) => {
+                        let (start, end) = self.scopes[i];
+                        (start.line, start.col) <= (line, col) && (line, col) <= (end.line, end.col)
+                    }
+                    None => true,
+                };
+                if defined && in_scope {
+                    visible.insert(ident.value.clone(), info.clone());
+                }
             }
+            visible
+        }
+        /// Get the spans of all references to the binding defined at a span,
+        /// including the definition
+        fn references(&self, definition: &CodeSpan) -> Vec<CodeSpan> {
+            (self.bindings.iter())
+                .filter(|(_, info)| same_span(&info.span, definition))
+                .map(|(ident, _)| ident.span.clone())
+                .collect()
         }
     }
 
     pub struct BindingInfo {
         pub span: CodeSpan,
         pub comment: Option<String>,
+        /// The index of the scope the binding is in, if it is not top-level
+        pub scope: Option<usize>,
     }
 
-    fn bindings_info(items: &[Item]) -> BindingsInfo {
-        let mut bindings = BindingsInfo::new();
-        let mut scope_bindings = Vec::new();
-        let mut last_comment: Option<String> = None;
-        for item in items {
-            match item {
-                Item::Scoped { items, .. } => scope_bindings.push(bindings_info(items)),
-                Item::Words(words) => {
-                    if let [Sp {
-                        value: Word::Comment(comment),
-                        ..
-                    }] = words.as_slice()
-                    {
-                        let full = last_comment.get_or_insert_with(String::new);
-                        if !full.is_empty() {
-                            if comment.trim().is_empty() {
-                                full.push('\n');
-                                full.push('\n');
-                            } else {
-                                full.push(' ');
-                            }
+    /// An import of a file whose path is a string literal
+    pub struct ImportInfo {
+        pub span: CodeSpan,
+        pub path: String,
+    }
+
+    /// A [`Primitive::Use`] of a string literal on a binding to an import
+    pub struct UseInfo {
+        /// The span of the name, without quotes
+        pub span: CodeSpan,
+        pub name: Ident,
+        pub path: String,
+    }
+
+    #[derive(Default)]
+    struct BindingsBuilder {
+        bindings: BindingsInfo,
+        imports: Vec<ImportInfo>,
+        uses: Vec<UseInfo>,
+        scopes: Vec<Scope>,
+        scope_spans: Vec<(Loc, Loc)>,
+    }
+
+    #[derive(Default)]
+    struct Scope {
+        index: Option<usize>,
+        names: HashMap<Ident, Arc<BindingInfo>>,
+        /// The paths of imports that are bound to names
+        imports: HashMap<Ident, String>,
+    }
+
+    impl BindingsBuilder {
+        fn items(&mut self, items: &[Item]) {
+            let mut last_comment: Option<String> = None;
+            for item in items {
+                match item {
+                    Item::Scoped { items, .. } => {
+                        let index = self.scope_spans.len();
+                        if let Some(span) = items_extent(items) {
+                            self.scope_spans.push(span);
                         }
-                        full.push_str(comment.trim());
-                    } else {
-                        last_comment = None;
-                        for word in words {
-                            if let Word::Ident(ident) = &word.value {
-                                if let Some((_, info)) =
-                                    bindings.iter().rev().find(|(name, _)| name.value == *ident)
-                                {
-                                    let info = info.clone();
-                                    bindings.insert(word.span.clone().sp(ident.clone()), info);
+                        self.scopes.push(Scope {
+                            index: Some(index).filter(|&i| i < self.scope_spans.len()),
+                            ..Default::default()
+                        });
+                        self.items(items);
+                        self.scopes.pop();
+                    }
+                    Item::Words(words) => {
+                        if let [Sp {
+                            value: Word::Comment(comment),
+                            ..
+                        }] = words.as_slice()
+                        {
+                            let full = last_comment.get_or_insert_with(String::new);
+                            if !full.is_empty() {
+                                if comment.trim().is_empty() {
+                                    full.push('\n');
+                                    full.push('\n');
+                                } else {
+                                    full.push(' ');
                                 }
                             }
+                            full.push_str(comment.trim());
+                        } else {
+                            last_comment = None;
+                            self.words(words);
                         }
                     }
-                }
-                Item::Binding(binding) => {
-                    let comment = last_comment.take();
-                    bindings.insert(
-                        binding.name.clone(),
-                        BindingInfo {
+                    Item::Binding(binding) => {
+                        let comment = last_comment.take();
+                        self.words(&binding.words);
+                        let info = Arc::new(BindingInfo {
                             comment,
                             span: binding.name.span.clone(),
+                            scope: self.scopes.last().unwrap().index,
+                        });
+                        self.bindings.insert(binding.name.clone(), info.clone());
+                        let name = binding.name.value.clone();
+                        let scope = self.scopes.last_mut().unwrap();
+                        match significant(&binding.words).as_slice() {
+                            [Sp {
+                                value: Word::Primitive(Primitive::Sys(SysOp::Import)),
+                                ..
+                            }, Sp {
+                                value: Word::String(path),
+                                ..
+                            }] => scope.imports.insert(name.clone(), path.clone()),
+                            _ => scope.imports.remove(&name),
+                        };
+                        scope.names.insert(name, info);
+                    }
+                    Item::ExtraNewlines(_) => {}
+                }
+            }
+        }
+        /// Find the scope that a name is visible in
+        ///
+        /// Like the interpreter, scopes can see the names of the scope directly above them.
+        fn scope_of(&self, name: &Ident) -> Option<&Scope> {
+            (self.scopes.iter().rev().take(2)).find(|scope| scope.names.contains_key(name))
+        }
+        fn words(&mut self, words: &[Sp<Word>]) {
+            let words = significant(words);
+            for (i, word) in words.iter().enumerate() {
+                match &word.value {
+                    Word::Ident(ident) => {
+                        if let Some(info) = self.scope_of(ident).map(|scope| &scope.names[ident]) {
+                            let info = info.clone();
+                            self.bindings
+                                .insert(word.span.clone().sp(ident.clone()), info);
+                        }
+                    }
+                    Word::Primitive(Primitive::Sys(SysOp::Import)) => {
+                        if let Some(Sp {
+                            value: Word::String(path),
+                            span,
+                        }) = words.get(i + 1)
+                        {
+                            self.imports.push(ImportInfo {
+                                span: span.clone(),
+                                path: path.clone(),
+                            });
                         }
-                        .into(),
-                    );
+                    }
+                    Word::Primitive(Primitive::Use) => {
+                        if let [Sp {
+                            value: Word::String(name),
+                            span,
+                        }, Sp {
+                            value: Word::Ident(lib),
+                            ..
+                        }, ..] = &words[i + 1..]
+                        {
+                            let path = self.scope_of(lib).and_then(|scope| scope.imports.get(lib));
+                            if let Some(path) = path {
+                                self.uses.push(UseInfo {
+                                    span: unquoted(span),
+                                    name: name.as_str().into(),
+                                    path: path.clone(),
+                                });
+                            }
+                        }
+                    }
+                    Word::Strand(items) => self.words(items),
+                    Word::Array(arr) => arr.lines.iter().for_each(|line| self.words(line)),
+                    Word::Func(func) => func.lines.iter().for_each(|line| self.words(line)),
+                    Word::Modified(m) => self.words(&m.operands),
+                    _ => {}
                 }
-                Item::ExtraNewlines(_) => {}
             }
         }
-        scope_bindings.push(bindings);
-        scope_bindings.into_iter().flatten().collect()
+    }
+
+    /// Get the start and end of some items
+    fn items_extent(items: &[Item]) -> Option<(Loc, Loc)> {
+        fn extent(item: &Item) -> Option<(Loc, Loc)> {
+            match item {
+                Item::Scoped { items, .. } => items_extent(items),
+                Item::Words(words) => Some((words.first()?.span.start, words.last()?.span.end)),
+                Item::Binding(binding) => Some((
+                    binding.name.span.start,
+                    (binding.words.last()).map_or(binding.name.span.end, |w| w.span.end),
+                )),
+                Item::ExtraNewlines(span) => Some((span.start, span.end)),
+            }
+        }
+        let start = items.iter().find_map(extent)?.0;
+        let end = items.iter().rev().find_map(extent)?.1;
+        Some((start, end))
+    }
+
+    /// Get the words that are not whitespace or comments
+    fn significant(words: &[Sp<Word>]) -> Vec<&Sp<Word>> {
+        (words.iter())
+            .filter(|word| !matches!(word.value, Word::Spaces | Word::Comment(_)))
+            .collect()
+    }
+
+    /// Get the span of a string literal without its quotes
+    fn unquoted(span: &CodeSpan) -> CodeSpan {
+        let mut span = span.clone();
+        for (loc, delta) in [(&mut span.start, 1), (&mut span.end, -1isize)] {
+            loc.char_pos = loc.char_pos.saturating_add_signed(delta);
+            loc.byte_pos = loc.byte_pos.saturating_add_signed(delta);
+            loc.col = loc.col.saturating_add_signed(delta);
+        }
+        span
+    }
+
+    fn same_span(a: &CodeSpan, b: &CodeSpan) -> bool {
+        a.start == b.start && a.end == b.end
     }
 
     pub fn run_server() {
+        run_server_with_natives(Vec::new())
+    }
+
+    /// Run the language server with knowledge of some bindings created with [`Uiua::bind_native`]
+    ///
+    /// Hovering over the names of these bindings shows their documentation.
+    pub fn run_server_with_natives(natives: Vec<NativeBinding>) {
         tokio::runtime::Builder::new_current_thread()
             .build()
             .unwrap()
@@ -210,6 +421,7 @@ mod server {
                 let (service, socket) = LspService::new(|client| Backend {
                     client,
                     docs: DashMap::new(),
+                    natives,
                 });
                 Server::new(stdin, stdout, socket).serve(service).await;
             });
@@ -218,6 +430,636 @@ mod server {
     struct Backend {
         client: Client,
         docs: DashMap<Url, LspDoc>,
+        natives: Vec<NativeBinding>,
+    }
+
+    /// Something that can be shown in hovers and signature help
+    enum Documented<'a> {
+        Prim(Primitive),
+        Binding(&'a Ident, &'a BindingInfo, Option<Signature>),
+        Native(&'a NativeBinding),
+    }
+
+    impl<'a> Documented<'a> {
+        /// Get the label, along with the offsets of any operands in it
+        fn label(&self) -> (String, Vec<[u32; 2]>) {
+            let mut operands = Vec::new();
+            let label = match self {
+                Documented::Prim(prim) => {
+                    let name = prim.name().unwrap_or_default();
+                    let mut label = match prim.glyph() {
+                        Some(glyph) => format!("{glyph} {name}"),
+                        None => name.into(),
+                    };
+                    if let Some(sigs) = prim.operand_signatures() {
+                        for (i, sig) in sigs.iter().enumerate() {
+                            label.push_str(if i == 0 { " " } else { ", " });
+                            let start = label.encode_utf16().count() as u32;
+                            label.push_str(sig);
+                            operands.push([start, label.encode_utf16().count() as u32]);
+                        }
+                    } else if let Some((args, outputs)) = prim.args().zip(prim.outputs()) {
+                        let sig = Signature::new(args as usize, outputs as usize);
+                        label.push_str(&format!(" {sig}"));
+                    }
+                    label
+                }
+                Documented::Binding(name, _, Some(sig)) => format!("{name} {sig}"),
+                Documented::Binding(name, _, None) => name.to_string(),
+                Documented::Native(native) => format!("{} {}", native.name, native.signature),
+            };
+            (label, operands)
+        }
+        /// Get the documentation as markdown
+        fn markdown(&self) -> String {
+            match self {
+                Documented::Prim(prim) => {
+                    let mut markdown = String::new();
+                    if let Primitive::Sys(op) = prim {
+                        markdown.push_str(&format!("*{}*\n\n", op.long_name()));
+                    }
+                    if let Some(doc) = prim.doc() {
+                        markdown.push_str(&doc_markdown(doc));
+                    }
+                    markdown
+                }
+                Documented::Binding(_, info, _) => info.comment.clone().unwrap_or_default(),
+                Documented::Native(native) => native.doc.clone(),
+            }
+        }
+    }
+
+    /// The definition of a binding
+    struct Definition {
+        uri: Url,
+        span: CodeSpan,
+        name: Ident,
+    }
+
+    impl Backend {
+        /// Run a function on a document, reading it from disk if it is not open
+        fn with_doc<T>(&self, uri: &Url, f: impl FnOnce(&LspDoc) -> T) -> Option<T> {
+            if let Some(doc) = self.docs.get(uri) {
+                return Some(f(&doc));
+            }
+            let input = fs::read_to_string(uri.to_file_path().ok()?).ok()?;
+            Some(f(&LspDoc::new(input)))
+        }
+        /// Check a document, publish its diagnostics, and update its signature hints
+        async fn check_doc(&self, uri: Url, input: String, version: i32) {
+            let path = uri.to_file_path().ok();
+            let checked = {
+                let input = input.clone();
+                tokio::task::spawn_blocking(move || check(&input, path.as_deref())).await
+            };
+            let Ok(Checked {
+                diagnostics,
+                hints,
+                signatures,
+            }) = checked
+            else {
+                return;
+            };
+            self.client
+                .publish_diagnostics(uri.clone(), diagnostics, Some(version))
+                .await;
+            if let Some(mut doc) = self.docs.get_mut(&uri) {
+                if doc.input == input {
+                    doc.hints = hints;
+                    doc.signatures = signatures;
+                }
+            }
+            _ = self.client.inlay_hint_refresh().await;
+        }
+        /// Find the last thing that can be documented whose span matches a predicate
+        fn documented_at<'a>(
+            &'a self,
+            doc: &'a LspDoc,
+            matches: impl Fn(&CodeSpan) -> bool,
+        ) -> Option<(Documented<'a>, CodeSpan)> {
+            let natives = (doc.spans.iter())
+                .filter(|sp| sp.value == SpanKind::Ident && matches(&sp.span))
+                .filter_map(|sp| {
+                    let native = self.natives.iter().find(|n| &*n.name == sp.span.as_str())?;
+                    Some((Documented::Native(native), sp.span.clone()))
+                });
+            let bindings = (doc.bindings.iter())
+                .filter(|(ident, _)| matches(&ident.span))
+                .map(|(ident, info)| {
+                    let sig = (doc.signatures.iter())
+                        .find(|(span, _)| same_span(span, &info.span))
+                        .map(|(_, sig)| *sig);
+                    (
+                        Documented::Binding(&ident.value, info, sig),
+                        ident.span.clone(),
+                    )
+                });
+            let prims = doc.spans.iter().filter_map(|sp| match sp.value {
+                SpanKind::Primitive(prim) if prim.name().is_some() && matches(&sp.span) => {
+                    Some((Documented::Prim(prim), sp.span.clone()))
+                }
+                _ => None,
+            });
+            (natives.chain(bindings).chain(prims)).max_by_key(|(_, span)| span.start)
+        }
+        /// Find the definition of the binding referenced at a position
+        ///
+        /// Names in [`Primitive::Use`]s of imported files refer to bindings in those files.
+        fn definition(&self, uri: &Url, pos: Position) -> Option<Definition> {
+            let (line, col) = lsp_pos_to_uiua(pos);
+            let (name, path) = {
+                let doc = self.docs.get(uri)?;
+                if let Some((ident, info)) = doc.binding_at(line, col) {
+                    return Some(Definition {
+                        uri: uri.clone(),
+                        span: info.span.clone(),
+                        name: ident.value.clone(),
+                    });
+                }
+                let used = (doc.uses.iter()).find(|u| u.span.contains_line_col(line, col))?;
+                (used.name.clone(), used.path.clone())
+            };
+            let target = import_uri(uri, &path)?;
+            let span = self.with_doc(&target, |doc| {
+                doc.exports.get(&name).map(|info| info.span.clone())
+            })??;
+            Some(Definition {
+                uri: target,
+                span,
+                name,
+            })
+        }
+        /// Find all references to a binding, including its definition
+        ///
+        /// Uses of the binding are only found in open files.
+        fn references(&self, def: &Definition) -> Vec<Location> {
+            let (spans, exported) = self
+                .with_doc(&def.uri, |doc| {
+                    let exported = (doc.exports.get(&def.name))
+                        .is_some_and(|info| same_span(&info.span, &def.span));
+                    (doc.references(&def.span), exported)
+                })
+                .unwrap_or_default();
+            let mut locations: Vec<Location> = (spans.iter())
+                .map(|span| Location::new(def.uri.clone(), uiua_span_to_lsp(span)))
+                .collect();
+            if exported {
+                for doc in self.docs.iter() {
+                    for used in &doc.uses {
+                        if used.name == def.name
+                            && import_uri(doc.key(), &used.path).as_ref() == Some(&def.uri)
+                        {
+                            locations.push(Location::new(
+                                doc.key().clone(),
+                                uiua_span_to_lsp(&used.span),
+                            ));
+                        }
+                    }
+                }
+            }
+            locations
+        }
+    }
+
+    /// The backend used to check documents
+    ///
+    /// Printing does nothing, and files can be read so that imports work.
+    /// All other system functions fail.
+    struct CheckSys;
+
+    impl SysBackend for CheckSys {
+        fn any(&self) -> &dyn Any {
+            self
+        }
+        fn print_str_stdout(&self, _: &str) -> std::result::Result<(), String> {
+            Ok(())
+        }
+        fn print_str_stderr(&self, _: &str) -> std::result::Result<(), String> {
+            Ok(())
+        }
+        fn print_str_trace(&self, _: &str) {}
+        fn file_exists(&self, path: &str) -> bool {
+            NativeSys.file_exists(path)
+        }
+        fn is_file(&self, path: &str) -> std::result::Result<bool, String> {
+            NativeSys.is_file(path)
+        }
+        fn list_dir(&self, path: &str) -> std::result::Result<Vec<String>, String> {
+            NativeSys.list_dir(path)
+        }
+        fn file_read_all(&self, path: &str) -> std::result::Result<Vec<u8>, String> {
+            NativeSys.file_read_all(path)
+        }
+    }
+
+    /// The results of checking a document
+    struct Checked {
+        diagnostics: Vec<lsp_types::Diagnostic>,
+        hints: Vec<InlayHint>,
+        signatures: Vec<(CodeSpan, Signature)>,
+    }
+
+    /// Check a document by running it
+    ///
+    /// Errors from system functions are not reported, because they
+    /// are not actually run. Neither are errors from exceeding the time
+    /// or memory limits of the check.
+    fn check(input: &str, path: Option<&Path>) -> Checked {
+        let mut env = Uiua::with_backend(CheckSys)
+            .with_mode(RunMode::All)
+            .with_execution_limit(Duration::from_secs(1))
+            .with_memory_limit(1 << 28);
+        env.inferred_signatures = Some(Vec::new());
+        let res = match path {
+            Some(path) => env.load_str_path(input, path),
+            None => env.load_str(input),
+        };
+        let in_doc = |span: &CodeSpan| span.path.as_deref() == path;
+        let mut diagnostics = Vec::new();
+        if let Err(error) = res {
+            for (message, span) in error_spans(error) {
+                let is_sys_op = SysOp::ALL.iter().any(|op| op.name() == span.as_str());
+                if in_doc(&span) && !is_sys_op {
+                    diagnostics.push(lsp_types::Diagnostic {
+                        severity: Some(DiagnosticSeverity::ERROR),
+                        ..lsp_types::Diagnostic::new_simple(uiua_span_to_lsp(&span), message)
+                    });
+                }
+            }
+        }
+        for diag in env.take_diagnostics() {
+            let Span::Code(span) = &diag.span else {
+                continue;
+            };
+            if in_doc(span) {
+                let severity = match diag.kind {
+                    DiagnosticKind::Warning => DiagnosticSeverity::WARNING,
+                    DiagnosticKind::Advice => DiagnosticSeverity::INFORMATION,
+                    DiagnosticKind::Style => DiagnosticSeverity::HINT,
+                };
+                diagnostics.push(lsp_types::Diagnostic {
+                    severity: Some(severity),
+                    ..lsp_types::Diagnostic::new_simple(uiua_span_to_lsp(span), diag.message)
+                });
+            }
+        }
+        let mut hints = BTreeMap::new();
+        let mut signatures = Vec::new();
+        for sig in env.inferred_signatures.take().unwrap_or_default() {
+            if !in_doc(&sig.span) {
+                continue;
+            }
+            // Bindings without arguments are bound to values rather than functions
+            let is_binding = !sig.span.as_str().starts_with('(');
+            if is_binding && (sig.declared.is_some() || sig.inferred.args > 0) {
+                let signature = sig.declared.unwrap_or(sig.inferred);
+                signatures.push((sig.span.clone(), signature));
+            }
+            if let Some(hint) = signature_hint(input, sig) {
+                hints.insert((hint.position.line, hint.position.character), hint);
+            }
+        }
+        Checked {
+            diagnostics,
+            hints: hints.into_values().collect(),
+            signatures,
+        }
+    }
+
+    /// Get the inlay hint for an inferred signature
+    ///
+    /// Hints are shown where a signature could be declared.
+    /// Bindings without arguments are not hinted unless their declared signature
+    /// is wrong, because they are bound to their values.
+    fn signature_hint(input: &str, sig: InferredSignature) -> Option<InlayHint> {
+        let is_func = sig.span.as_str().starts_with('(');
+        let mismatch = sig
+            .declared
+            .filter(|declared| !declared.is_superset_of(sig.inferred));
+        if sig.declared.is_some() && mismatch.is_none() {
+            return None;
+        }
+        if !is_func && sig.inferred.args == 0 && mismatch.is_none() {
+            return None;
+        }
+        let mut position = if is_func {
+            uiua_loc_to_lsp(sig.span.start)
+        } else {
+            uiua_loc_to_lsp(sig.span.end)
+        };
+        if is_func {
+            position.character += 1;
+        } else {
+            let after = input[sig.span.end.byte_pos..].chars();
+            let spaces = after.clone().take_while(|&c| c == ' ' || c == '\t').count();
+            let arrow = (after.clone().nth(spaces)).is_some_and(|c| c == '←' || c == '=');
+            position.character += (spaces + arrow as usize) as u32;
+        }
+        let (label, tooltip) = if let Some(declared) = mismatch {
+            (
+                format!("{} ≠", sig.inferred),
+                format!(
+                    "The declared signature {declared} does not match \
+                     the inferred signature {}",
+                    sig.inferred
+                ),
+            )
+        } else {
+            (sig.inferred.to_string(), "Inferred signature".into())
+        };
+        Some(InlayHint {
+            position,
+            label: InlayHintLabel::String(label),
+            kind: Some(InlayHintKind::TYPE),
+            text_edits: None,
+            tooltip: Some(InlayHintTooltip::String(tooltip)),
+            padding_left: Some(true),
+            padding_right: Some(true),
+            data: None,
+        })
+    }
+
+    /// Get the messages and spans of an error
+    fn error_spans(error: UiuaError) -> Vec<(String, CodeSpan)> {
+        match error {
+            UiuaError::Parse(errors) => (errors.into_iter())
+                .map(|error| (error.value.to_string(), error.span))
+                .collect(),
+            UiuaError::Run(Sp {
+                value,
+                span: Span::Code(span),
+            }) => vec![(value, span)],
+            UiuaError::Throw(value, Span::Code(span)) => vec![(value.to_string(), span)],
+            UiuaError::Break(_, Span::Code(span)) => {
+                vec![("break outside of loop".into(), span)]
+            }
+            UiuaError::Traced { error, .. } | UiuaError::Fill(error) => error_spans(*error),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Get the short documentation of a primitive as plain text
+    fn doc_summary(doc: &PrimDoc) -> String {
+        (doc.short.iter())
+            .map(|frag| match frag {
+                PrimDocFragment::Text(text)
+                | PrimDocFragment::Code(text)
+                | PrimDocFragment::Emphasis(text)
+                | PrimDocFragment::Strong(text)
+                | PrimDocFragment::Link { text, .. } => text.clone(),
+                PrimDocFragment::Primitive { prim, named } => {
+                    let name = prim.name().unwrap();
+                    if *named {
+                        if let Some(unicode) = prim.glyph() {
+                            format!("{} {}", unicode, name)
+                        } else {
+                            name.into()
+                        }
+                    } else if let Some(unicode) = prim.glyph() {
+                        unicode.into()
+                    } else {
+                        name.into()
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Get the documentation of a primitive as markdown
+    ///
+    /// Examples are rendered as code blocks.
+    fn doc_markdown(doc: &PrimDoc) -> String {
+        let mut markdown = frags_markdown(&doc.short);
+        for line in &doc.lines {
+            match line {
+                PrimDocLine::Text(frags) if frags.is_empty() => {}
+                PrimDocLine::Text(frags) => {
+                    markdown.push_str("\n\n");
+                    markdown.push_str(&frags_markdown(frags));
+                }
+                PrimDocLine::Example(ex) => {
+                    markdown.push_str(&format!("\n\n```uiua\n{}\n```", ex.input()));
+                }
+            }
+        }
+        markdown
+    }
+
+    fn frags_markdown(frags: &[PrimDocFragment]) -> String {
+        (frags.iter())
+            .map(|frag| match frag {
+                PrimDocFragment::Text(text) => text.clone(),
+                PrimDocFragment::Code(code) => format!("`{code}`"),
+                PrimDocFragment::Emphasis(text) => format!("*{text}*"),
+                PrimDocFragment::Strong(text) => format!("**{text}**"),
+                PrimDocFragment::Link { text, url } => format!("[{text}]({url})"),
+                PrimDocFragment::Primitive { prim, named } => {
+                    let name = prim.name().unwrap_or_default();
+                    match prim.glyph() {
+                        Some(glyph) if *named => format!("`{glyph}` {name}"),
+                        Some(glyph) => format!("`{glyph}`"),
+                        None => format!("`{name}`"),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Get completions for the word that ends at a position
+    ///
+    /// Primitives are completed by name or ASCII spelling and replaced with their glyphs.
+    fn completions(doc: &LspDoc, pos: Position) -> Vec<CompletionItem> {
+        let line = doc.input.lines().nth(pos.line as usize).unwrap_or_default();
+        let before: Vec<char> = line.chars().take(pos.character as usize).collect();
+        let word_len = |f: fn(&char) -> bool| before.iter().rev().take_while(|c| f(c)).count();
+        let mut len = word_len(|&c| is_ident_char(c));
+        if before.len() > len && before[before.len() - len - 1] == '&' {
+            len += 1;
+        }
+        let ascii = len == 0;
+        if ascii {
+            len = word_len(|c| "!=<>*%^`".contains(*c));
+        }
+        let prefix: String = before[before.len() - len..].iter().collect();
+        let lower_prefix = prefix.to_lowercase();
+        let range = Range::new(Position::new(pos.line, pos.character - len as u32), pos);
+        let item = |label: String, kind, detail: String, doc: Option<String>, text: String| {
+            CompletionItem {
+                label,
+                kind: Some(kind),
+                detail: Some(detail),
+                documentation: doc.map(Documentation::String),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(range, text))),
+                ..Default::default()
+            }
+        };
+        let mut items = Vec::new();
+        for prim in Primitive::non_deprecated() {
+            let Some(name) = prim.name() else {
+                continue;
+            };
+            let kind = if prim.as_constant().is_some() {
+                CompletionItemKind::CONSTANT
+            } else if prim.is_modifier() {
+                CompletionItemKind::OPERATOR
+            } else {
+                CompletionItemKind::FUNCTION
+            };
+            let summary = prim.doc().map(doc_summary);
+            if let Primitive::Sys(op) = prim {
+                if !ascii && name.starts_with(&prefix) {
+                    let detail = op.long_name().into();
+                    items.push(item(name.into(), kind, detail, summary, name.into()));
+                }
+                continue;
+            }
+            let text = prim.glyph().map_or_else(|| name.into(), String::from);
+            let detail = format!("{text} {name}");
+            let spelling = if ascii {
+                prim.ascii().map(|ascii| ascii.to_string())
+            } else {
+                Some(name.into())
+            };
+            if let Some(spelling) = spelling.filter(|s| s.starts_with(&lower_prefix)) {
+                items.push(item(spelling, kind, detail, summary, text));
+            }
+        }
+        if !ascii {
+            let (line, col) = lsp_pos_to_uiua(pos);
+            for (name, info) in doc.bindings_in_scope(line, col) {
+                if name.starts_with(&prefix) {
+                    let comment = info.comment.clone();
+                    let kind = CompletionItemKind::VARIABLE;
+                    let name = name.to_string();
+                    items.push(item(name.clone(), kind, name.clone(), comment, name));
+                }
+            }
+        }
+        items
+    }
+
+    /// Format the items that overlap a range of lines
+    ///
+    /// The range is expanded to whole items, so a range inside a scope only formats
+    /// items in that scope, and a range that touches a scope formats all of it.
+    fn format_range(input: &str, range: Range, config: &FormatConfig) -> Option<TextEdit> {
+        let (items, errors, _) = parse(input, None);
+        if !errors.is_empty() {
+            return None;
+        }
+        let lines: Vec<&str> = input.lines().collect();
+        let first = range.start.line as usize;
+        let mut last = range.end.line as usize;
+        if range.end.character == 0 && last > first {
+            last -= 1;
+        }
+        if first >= lines.len() {
+            return None;
+        }
+        let last = last.min(lines.len() - 1);
+        let (first, last) = item_lines(&items, &lines, first, last);
+        let old = lines[first..=last].join("\n");
+        let config = FormatConfig {
+            trailing_newline: false,
+            ..config.clone()
+        };
+        let formatted = format_str(&old, &config).ok()?;
+        minimal_edit(&old, &formatted.output, Position::new(first as u32, 0))
+    }
+
+    /// Expand a range of 0-indexed lines to cover every item it overlaps
+    fn item_lines(items: &[Item], lines: &[&str], first: usize, last: usize) -> (usize, usize) {
+        let is_delim = |line: &&str| matches!(line.trim(), "---" | "~~~");
+        let (mut first, mut last) = (first, last);
+        for item in items {
+            let Some((start, end)) = items_extent(slice::from_ref(item)) else {
+                continue;
+            };
+            let (mut start, mut end) = (start.line - 1, end.line - 1);
+            if let Item::Scoped { items, .. } = item {
+                if start <= first && last <= end {
+                    return item_lines(items, lines, first, last);
+                }
+                start = lines[..start].iter().rposition(is_delim).unwrap_or(start);
+                end = (lines[end + 1..].iter().position(is_delim)).map_or(end, |i| end + 1 + i);
+            }
+            if start <= last && first <= end {
+                first = first.min(start);
+                last = last.max(end);
+            }
+        }
+        (first, last)
+    }
+
+    /// Replace a primitive name that was just typed with its glyph
+    fn format_on_type(doc: &LspDoc, pos: Position) -> Option<TextEdit> {
+        let line = doc.input.lines().nth(pos.line as usize)?;
+        let before: Vec<char> = (line.chars())
+            .take(pos.character.checked_sub(1)? as usize)
+            .collect();
+        let len = (before.iter().rev())
+            .take_while(|&&c| is_ident_char(c))
+            .count();
+        if len == 0 {
+            return None;
+        }
+        let start = Position::new(pos.line, (before.len() - len) as u32);
+        // Only words that parsed as primitives are formatted,
+        // which leaves bindings, strings, and comments alone
+        let (line, col) = lsp_pos_to_uiua(start);
+        doc.spans.iter().find(|span| {
+            matches!(span.value, SpanKind::Primitive(_))
+                && span.span.start.line == line
+                && span.span.start.col == col
+        })?;
+        let word: String = before[before.len() - len..].iter().collect();
+        let config = FormatConfig {
+            trailing_newline: false,
+            ..Default::default()
+        };
+        let formatted = format_str(&word, &config).ok()?;
+        let end = formatted.map_char_pos(len);
+        let new: String = formatted.output.chars().take(end).collect();
+        minimal_edit(&word, &new, start)
+    }
+
+    /// Get an edit that only replaces the part of some text that changed
+    ///
+    /// Leaving the rest of the text alone keeps the cursor in place.
+    fn minimal_edit(old: &str, new: &str, start: Position) -> Option<TextEdit> {
+        let old: Vec<char> = old.chars().collect();
+        let new: Vec<char> = new.chars().collect();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        if prefix == old.len() && prefix == new.len() {
+            return None;
+        }
+        let suffix = (old[prefix..].iter().rev())
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let start = advance(start, &old[..prefix]);
+        let end = advance(start, &old[prefix..old.len() - suffix]);
+        let text = new[prefix..new.len() - suffix].iter().collect();
+        Some(TextEdit::new(Range::new(start, end), text))
+    }
+
+    /// Get the position after some text that starts at a position
+    fn advance(mut pos: Position, text: &[char]) -> Position {
+        for &c in text {
+            if c == '\n' {
+                pos.line += 1;
+                pos.character = 0;
+            } else {
+                pos.character += 1;
+            }
+        }
+        pos
+    }
+
+    /// Get the URI of a file imported from a document
+    fn import_uri(uri: &Url, path: &str) -> Option<Url> {
+        let path = uri.to_file_path().ok()?.parent()?.join(path);
+        Url::from_file_path(path.canonicalize().unwrap_or(path)).ok()
     }
 
     #[tower_lsp::async_trait]
@@ -229,7 +1071,24 @@ mod server {
                         TextDocumentSyncKind::FULL,
                     )),
                     hover_provider: Some(HoverProviderCapability::Simple(true)),
+                    inlay_hint_provider: Some(OneOf::Left(true)),
+                    signature_help_provider: Some(SignatureHelpOptions {
+                        trigger_characters: Some(vec![" ".into()]),
+                        ..Default::default()
+                    }),
+                    completion_provider: Some(CompletionOptions {
+                        trigger_characters: Some(vec!["&".into()]),
+                        ..Default::default()
+                    }),
+                    definition_provider: Some(OneOf::Left(true)),
+                    references_provider: Some(OneOf::Left(true)),
+                    rename_provider: Some(OneOf::Left(true)),
                     document_formatting_provider: Some(OneOf::Left(true)),
+                    document_range_formatting_provider: Some(OneOf::Left(true)),
+                    document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                        first_trigger_character: " ".into(),
+                        more_trigger_character: None,
+                    }),
                     semantic_tokens_provider: Some(
                         SemanticTokensServerCapabilities::SemanticTokensOptions(
                             SemanticTokensOptions {
@@ -260,96 +1119,167 @@ mod server {
         }
 
         async fn did_open(&self, param: DidOpenTextDocumentParams) {
-            self.docs.insert(
-                param.text_document.uri,
-                LspDoc::new(param.text_document.text),
-            );
+            let doc = param.text_document;
+            self.docs
+                .insert(doc.uri.clone(), LspDoc::new(doc.text.clone()));
+            self.check_doc(doc.uri, doc.text, doc.version).await;
         }
 
         async fn did_change(&self, params: DidChangeTextDocumentParams) {
-            self.docs.insert(
-                params.text_document.uri,
-                LspDoc::new(params.content_changes[0].text.clone()),
-            );
+            let uri = params.text_document.uri;
+            let text = params.content_changes[0].text.clone();
+            self.docs.insert(uri.clone(), LspDoc::new(text.clone()));
+            self.check_doc(uri, text, params.text_document.version)
+                .await;
         }
 
         async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-            let doc = if let Some(doc) = self
-                .docs
-                .get(&params.text_document_position_params.text_document.uri)
-            {
-                doc
-            } else {
+            let position = params.text_document_position_params;
+            let Some(doc) = self.docs.get(&position.text_document.uri) else {
                 return Ok(None);
             };
-            let (line, col) = lsp_pos_to_uiua(params.text_document_position_params.position);
-            let mut prim_range = None;
-            for sp in &doc.spans {
-                if sp.span.contains_line_col(line, col) {
-                    match sp.value {
-                        SpanKind::Primitive(prim) => {
-                            if prim.name().is_some() {
-                                prim_range = Some((prim, uiua_span_to_lsp(&sp.span)));
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-            let mut binding_range = None;
-            for (ident, binding) in &doc.bindings {
-                if ident.span.contains_line_col(line, col) {
-                    binding_range = Some((ident, binding, uiua_span_to_lsp(&ident.span)));
-                }
+            let (line, col) = lsp_pos_to_uiua(position.position);
+            let Some((documented, span)) =
+                self.documented_at(&doc, |span| span.contains_line_col(line, col))
+            else {
+                return Ok(None);
+            };
+            let (label, _) = documented.label();
+            let mut markdown = format!("```uiua\n{label}\n```");
+            let body = documented.markdown();
+            if !body.is_empty() {
+                markdown.push_str("\n\n");
+                markdown.push_str(&body);
             }
-            Ok(Some(if let Some((prim, range)) = prim_range {
-                let mut contents = vec![MarkedString::String(prim.name().unwrap().into())];
-                if let Some(doc) = prim.doc() {
-                    contents.push(MarkedString::String(
-                        doc.short
-                            .iter()
-                            .map(|frag| match frag {
-                                PrimDocFragment::Text(text)
-                                | PrimDocFragment::Code(text)
-                                | PrimDocFragment::Emphasis(text)
-                                | PrimDocFragment::Strong(text)
-                                | PrimDocFragment::Link { text, .. } => text.clone(),
-                                PrimDocFragment::Primitive { prim, named } => {
-                                    let name = prim.name().unwrap();
-                                    if *named {
-                                        if let Some(unicode) = prim.glyph() {
-                                            format!("{} {}", unicode, name)
-                                        } else {
-                                            name.into()
-                                        }
-                                    } else if let Some(unicode) = prim.glyph() {
-                                        unicode.into()
-                                    } else {
-                                        name.into()
-                                    }
-                                }
-                            })
-                            .collect(),
-                    ))
-                }
-                Hover {
-                    contents: HoverContents::Array(contents),
-                    range: Some(range),
-                }
-            } else if let Some((ident, binding, range)) = binding_range {
-                let mut contents = vec![MarkedString::String(ident.value.as_ref().into())];
-                if let Some(comment) = &binding.comment {
-                    contents.push(MarkedString::String(comment.clone()))
-                }
-                Hover {
-                    contents: HoverContents::Array(contents),
-                    range: Some(range),
-                }
-            } else {
+            Ok(Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: markdown,
+                }),
+                range: Some(uiua_span_to_lsp(&span)),
+            }))
+        }
+
+        async fn signature_help(
+            &self,
+            params: SignatureHelpParams,
+        ) -> Result<Option<SignatureHelp>> {
+            let position = params.text_document_position_params;
+            let Some(doc) = self.docs.get(&position.text_document.uri) else {
+                return Ok(None);
+            };
+            let (line, col) = lsp_pos_to_uiua(position.position);
+            // Show the function closest to the left of the cursor
+            let Some((documented, _)) =
+                self.documented_at(&doc, |span| span.start.line == line && span.start.col < col)
+            else {
+                return Ok(None);
+            };
+            let (label, operands) = documented.label();
+            let body = documented.markdown();
+            let parameters = (operands.into_iter())
+                .map(|offsets| ParameterInformation {
+                    label: ParameterLabel::LabelOffsets(offsets),
+                    documentation: None,
+                })
+                .collect();
+            Ok(Some(SignatureHelp {
+                signatures: vec![SignatureInformation {
+                    label,
+                    documentation: Some(Documentation::MarkupContent(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: body,
+                    })),
+                    parameters: Some(parameters),
+                    active_parameter: None,
+                }],
+                active_signature: Some(0),
+                active_parameter: None,
+            }))
+        }
+
+        async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+            let position = params.text_document_position;
+            let Some(doc) = self.docs.get(&position.text_document.uri) else {
+                return Ok(None);
+            };
+            let items = completions(&doc, position.position);
+            Ok(Some(CompletionResponse::Array(items)))
+        }
+
+        async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+            let Some(doc) = self.docs.get(&params.text_document.uri) else {
                 return Ok(None);
+            };
+            let range = params.range;
+            let hints = (doc.hints.iter())
+                .filter(|hint| range.start <= hint.position && hint.position <= range.end)
+                .cloned()
+                .collect();
+            Ok(Some(hints))
+        }
+
+        async fn goto_definition(
+            &self,
+            params: GotoDefinitionParams,
+        ) -> Result<Option<GotoDefinitionResponse>> {
+            let uri = &params.text_document_position_params.text_document.uri;
+            let pos = params.text_document_position_params.position;
+            if let Some(def) = self.definition(uri, pos) {
+                let range = uiua_span_to_lsp(&def.span);
+                return Ok(Some(GotoDefinitionResponse::Scalar(Location::new(
+                    def.uri, range,
+                ))));
+            }
+            let (line, col) = lsp_pos_to_uiua(pos);
+            let import = self.docs.get(uri).and_then(|doc| {
+                (doc.imports.iter())
+                    .find(|import| import.span.contains_line_col(line, col))
+                    .and_then(|import| import_uri(uri, &import.path))
+            });
+            Ok(import.map(|target| {
+                GotoDefinitionResponse::Scalar(Location::new(target, Range::default()))
             }))
         }
 
+        async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+            let position = params.text_document_position;
+            let Some(def) = self.definition(&position.text_document.uri, position.position) else {
+                return Ok(None);
+            };
+            let mut locations = self.references(&def);
+            if !params.context.include_declaration {
+                let range = uiua_span_to_lsp(&def.span);
+                locations.retain(|loc| loc.uri != def.uri || loc.range != range);
+            }
+            Ok(Some(locations))
+        }
+
+        async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+            let (items, errors, _) = parse(&params.new_name, None);
+            let is_ident = errors.is_empty()
+                && matches!(items.as_slice(), [Item::Words(words)]
+                    if matches!(significant(words).as_slice(), [Sp { value: Word::Ident(_), .. }]));
+            if !is_ident {
+                return Err(jsonrpc::Error::invalid_params(format!(
+                    "{:?} is not a valid name",
+                    params.new_name
+                )));
+            }
+            let position = params.text_document_position;
+            let Some(def) = self.definition(&position.text_document.uri, position.position) else {
+                return Ok(None);
+            };
+            let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+            for loc in self.references(&def) {
+                changes
+                    .entry(loc.uri)
+                    .or_default()
+                    .push(TextEdit::new(loc.range, params.new_name.clone()));
+            }
+            Ok(Some(WorkspaceEdit::new(changes)))
+        }
+
         async fn formatting(
             &self,
             params: DocumentFormattingParams,
@@ -385,11 +1315,35 @@ mod server {
             ) else {
                 return Ok(None);
             };
-            let range = Range::new(Position::new(0, 0), Position::new(u32::MAX, u32::MAX));
-            Ok(Some(vec![TextEdit {
-                range,
-                new_text: formatted.output,
-            }]))
+            let edit = minimal_edit(&doc.input, &formatted.output, Position::new(0, 0));
+            Ok(Some(edit.into_iter().collect()))
+        }
+
+        async fn range_formatting(
+            &self,
+            params: DocumentRangeFormattingParams,
+        ) -> Result<Option<Vec<TextEdit>>> {
+            let Some(doc) = self.docs.get(&params.text_document.uri) else {
+                return Ok(None);
+            };
+            let config = FormatConfig {
+                multiline_indent: params.options.tab_size as usize,
+                ..Default::default()
+            };
+            let edit = format_range(&doc.input, params.range, &config);
+            Ok(Some(edit.into_iter().collect()))
+        }
+
+        async fn on_type_formatting(
+            &self,
+            params: DocumentOnTypeFormattingParams,
+        ) -> Result<Option<Vec<TextEdit>>> {
+            let position = params.text_document_position;
+            let Some(doc) = self.docs.get(&position.text_document.uri) else {
+                return Ok(None);
+            };
+            let edit = format_on_type(&doc, position.position);
+            Ok(Some(edit.into_iter().collect()))
         }
 
         async fn inline_value(
@@ -492,4 +1446,173 @@ mod server {
     fn uiua_span_to_lsp(span: &CodeSpan) -> Range {
         uiua_locs_to_lsp(span.start, span.end)
     }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        fn names(spans: &[CodeSpan]) -> Vec<String> {
+            let mut names: Vec<String> = (spans.iter())
+                .map(|span| format!("{}:{}", span.start, span.as_str()))
+                .collect();
+            names.sort();
+            names
+        }
+
+        #[test]
+        fn binding_references() {
+            let doc = LspDoc::new("X ← 1\nF ← +X\n---\nX ← 2\n[X (F X)]\n---\n/+[X F ∘2]".into());
+            let (_, outer) = doc.binding_at(1, 1).unwrap();
+            assert_eq!(
+                names(&doc.references(&outer.span)),
+                ["1:1:X", "2:6:X", "7:4:X"]
+            );
+            let (_, inner) = doc.binding_at(5, 2).unwrap();
+            assert_eq!(
+                names(&doc.references(&inner.span)),
+                ["4:1:X", "5:2:X", "5:7:X"]
+            );
+            let (_, f) = doc.binding_at(5, 5).unwrap();
+            assert_eq!(names(&doc.references(&f.span)), ["2:1:F", "5:5:F", "7:6:F"]);
+        }
+
+        #[test]
+        fn check_diagnostics() {
+            let messages = |input: &str| -> Vec<(DiagnosticSeverity, u32, String)> {
+                (check(input, None).diagnostics.into_iter())
+                    .map(|diag| (diag.severity.unwrap(), diag.range.start.line, diag.message))
+                    .collect()
+            };
+            let diags = messages("&p \"hi\"\n&fwa \"lsp-check.txt\" \"hi\"");
+            assert!(diags.is_empty(), "{diags:?}");
+            assert!(!Path::new("lsp-check.txt").exists());
+            let diags = messages("&p \"hi\"\n(");
+            assert_eq!(diags.len(), 1, "{diags:?}");
+            assert_eq!(diags[0].0, DiagnosticSeverity::ERROR);
+            let diags = messages("x ← 1\nF ← |1 ++\n");
+            assert_eq!(diags.len(), 1, "{diags:?}");
+            assert_eq!(diags[0].1, 1);
+            assert!(diags[0].2.contains("signature mismatch"), "{diags:?}");
+            let diags = messages("↷ 1 2 3");
+            assert_eq!(diags.len(), 1, "{diags:?}");
+            assert_eq!(diags[0].0, DiagnosticSeverity::WARNING);
+            assert!(diags[0].2.contains("deprecated"), "{diags:?}");
+        }
+
+        #[test]
+        fn signature_hints() {
+            let hints = |input: &str| -> Vec<(u32, u32, String)> {
+                (check(input, None).hints.into_iter())
+                    .map(|hint| match hint.label {
+                        InlayHintLabel::String(label) => {
+                            (hint.position.line, hint.position.character, label)
+                        }
+                        _ => panic!("hint label is not a string"),
+                    })
+                    .collect()
+            };
+            assert_eq!(
+                hints("X ← 5\nF ← +1\nG ← |1.1 ⊢\n/(+×2) [1 2 3]"),
+                [(1, 3, "|1.1".into()), (3, 2, "|2.1".into()),]
+            );
+            assert_eq!(hints("F = |1 ++"), [(0, 3, "|3.1 ≠".into())]);
+        }
+
+        #[test]
+        fn documentation() {
+            let label = |prim| Documented::Prim(prim).label();
+            assert_eq!(label(Primitive::Add), ("+ add |2.1".into(), vec![]));
+            let (fork, operands) = label(Primitive::Fork);
+            assert_eq!(fork, "⊃ fork any, any");
+            assert_eq!(operands, [[7, 10], [12, 15]]);
+            let (print, _) = label(Primitive::Sys(SysOp::Print));
+            assert_eq!(print, "&p |1.0");
+            let markdown = Documented::Prim(Primitive::Sys(SysOp::Import)).markdown();
+            assert!(markdown.starts_with("*import*"), "{markdown}");
+            assert!(
+                markdown.contains("```uiua\nex ← &i \"example.ua\"\n"),
+                "{markdown}"
+            );
+        }
+
+        #[test]
+        fn completion() {
+            let complete = |input: &str, line: u32, character: u32| -> Vec<(String, String)> {
+                let doc = LspDoc::new(input.into());
+                (completions(&doc, Position::new(line, character)).into_iter())
+                    .map(|item| match item.text_edit {
+                        Some(CompletionTextEdit::Edit(edit)) => (item.label, edit.new_text),
+                        _ => panic!("completion has no edit"),
+                    })
+                    .collect()
+            };
+            let items = complete("rev", 0, 3);
+            assert!(items.contains(&("reverse".into(), "⇌".into())), "{items:?}");
+            let items = complete("1 != 2", 0, 4);
+            assert_eq!(items, [("!=".into(), "≠".into())]);
+            let items = complete("&fr", 0, 3);
+            assert!(items.iter().all(|(label, _)| label.starts_with("&fr")));
+            assert!(
+                items.contains(&("&frab".into(), "&frab".into())),
+                "{items:?}"
+            );
+            let input = "Foo ← 1\n---\nFab ← 2\nF\n---\nFib ← 3\nF";
+            let names = |items: Vec<(String, String)>| -> Vec<String> {
+                items
+                    .into_iter()
+                    .map(|(label, _)| label)
+                    .filter(|l| l.starts_with('F'))
+                    .collect()
+            };
+            assert_eq!(names(complete(input, 3, 1)), ["Fab", "Foo"]);
+            assert_eq!(names(complete(input, 6, 1)), ["Fib", "Foo"]);
+        }
+
+        #[test]
+        fn range_formatting() {
+            let edit = |input: &str, start: u32, end: u32| {
+                let range = Range::new(Position::new(start, 0), Position::new(end, 0));
+                let edit = format_range(input, range, &FormatConfig::default());
+                edit.map(|edit| (edit.range, edit.new_text))
+            };
+            let input = "x ← rev 1\n[1 2] len\n---\ny ← rev 2\nfirst [3 4]\n---\nneg 5";
+            let pos = Position::new;
+            assert_eq!(
+                edit(input, 1, 2),
+                Some((Range::new(pos(1, 6), pos(1, 9)), "⧻".into()))
+            );
+            assert_eq!(
+                edit(input, 4, 5),
+                Some((Range::new(pos(4, 0), pos(4, 5)), "⊢".into()))
+            );
+            let (range, text) = edit(input, 2, 3).unwrap();
+            assert_eq!(range, Range::new(pos(3, 4), pos(4, 5)));
+            assert_eq!(text, "⇌ 2\n⊢");
+            assert_eq!(edit("x ← 1\n(", 0, 1), None);
+            let doc = LspDoc::new("rev [1 2] \"rev \" Rev \nrev".into());
+            let on_type = |character| {
+                format_on_type(&doc, pos(0, character)).map(|edit| (edit.range, edit.new_text))
+            };
+            assert_eq!(
+                on_type(4),
+                Some((Range::new(pos(0, 0), pos(0, 3)), "⇌".into()))
+            );
+            assert_eq!(on_type(16), None);
+            assert_eq!(on_type(21), None);
+        }
+
+        #[test]
+        fn import_uses() {
+            let doc = LspDoc::new("ex ← &i \"example.ua\"\nSq ← use \"Square\" ex\nSq 5".into());
+            assert_eq!(doc.imports.len(), 1);
+            assert_eq!(doc.imports[0].path, "example.ua");
+            let [used] = doc.uses.as_slice() else {
+                panic!("expected one use")
+            };
+            assert_eq!(&*used.name, "Square");
+            assert_eq!(used.path, "example.ua");
+            assert_eq!(used.span.as_str(), "Square");
+            assert!(doc.exports.contains_key("Sq"));
+        }
+    }
 }
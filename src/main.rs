@@ -17,10 +17,13 @@ use instant::Instant;
 use notify::{EventKind, RecursiveMode, Watcher};
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
+use rustyline::{error::ReadlineError, DefaultEditor};
 use uiua::{
-    format::{format_file, FormatConfig, FormatConfigSource},
+    debug::{Breakpoint, Breakpoints, DebugCommand, Debugger, PauseReason},
+    format::{format_file, format_str, FormatConfig, FormatConfigSource},
+    lex::Span,
     run::RunMode,
-    Uiua, UiuaError, UiuaResult,
+    SandboxSys, Uiua, UiuaError, UiuaResult,
 };
 
 fn main() {
@@ -87,6 +90,9 @@ fn run() -> UiuaResult {
                 mode,
                 #[cfg(feature = "audio")]
                 audio_options,
+                sandbox_options,
+                no_optimize,
+                no_parallel,
                 args,
             } => {
                 if !no_update {
@@ -113,11 +119,18 @@ fn run() -> UiuaResult {
                 let mode = mode.unwrap_or(RunMode::Normal);
                 #[cfg(feature = "audio")]
                 setup_audio(audio_options);
-                let mut rt = Uiua::with_native_sys()
+                let rt = if sandbox_options.sandbox {
+                    sandbox_options.runtime()
+                } else {
+                    Uiua::with_native_sys()
+                };
+                let mut rt = rt
                     .with_mode(mode)
                     .with_file_path(&path)
                     .with_args(args)
-                    .print_diagnostics(true);
+                    .print_diagnostics(true)
+                    .with_optimizations(!no_optimize)
+                    .with_parallel_loops(!no_parallel);
                 rt.load_file(path)?;
                 for value in rt.take_stack() {
                     println!("{}", value.show());
@@ -186,6 +199,67 @@ fn run() -> UiuaResult {
                     eprintln!("Error watching file: {e}");
                 }
             }
+            App::Debug {
+                path,
+                breakpoints,
+                args,
+            } => {
+                let path = if let Some(path) = path {
+                    path
+                } else {
+                    match working_file_path() {
+                        Ok(path) => path,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return Ok(());
+                        }
+                    }
+                };
+                let input = fs::read_to_string(&path)
+                    .map_err(|e| UiuaError::Load(path.clone(), e.into()))?;
+                let mut debugger = Debugger::new({
+                    let input = input.clone();
+                    let path = path.clone();
+                    move |env, reason, breakpoints| {
+                        debug_prompt(env, reason, breakpoints, &input, &path)
+                    }
+                });
+                if breakpoints.is_empty() {
+                    debugger = debugger.pause_at_start();
+                }
+                for bp in breakpoints {
+                    match parse_breakpoint(&bp, &input, &path) {
+                        Some(bp) => debugger = debugger.with_breakpoint(bp),
+                        None => {
+                            eprintln!("Invalid breakpoint `{bp}`");
+                            return Ok(());
+                        }
+                    }
+                }
+                println!("{}", DEBUG_HELP.bright_black());
+                let mut rt = Uiua::with_native_sys()
+                    .with_mode(RunMode::Normal)
+                    .with_file_path(&path)
+                    .with_args(args)
+                    .with_debugger(debugger)
+                    .print_diagnostics(true);
+                rt.load_str_path(&input, &path)?;
+                for value in rt.take_stack() {
+                    println!("{}", value.show());
+                }
+            }
+            App::Repl {
+                formatter_options,
+                #[cfg(feature = "audio")]
+                audio_options,
+                args,
+            } => {
+                #[cfg(feature = "audio")]
+                setup_audio(audio_options);
+                let config =
+                    FormatConfig::from_source(formatter_options.format_config_source, None)?;
+                repl(config, args);
+            }
             #[cfg(feature = "lsp")]
             App::Lsp => uiua::lsp::run_server(),
         },
@@ -410,6 +484,299 @@ fn watch(
     }
 }
 
+const REPL_HELP: &str = "\
+Commands:
+  :help          Show this message
+  :clear         Clear the stack
+  :reset         Remove all bindings
+  :history       List previous inputs
+  :run <n>       Run input <n> from the history again
+  :quit          Exit the REPL
+Unclosed brackets continue the input on the next line.
+Use the up and down arrow keys to recall previous inputs.";
+
+fn repl(config: FormatConfig, args: Vec<String>) {
+    let new_rt = || {
+        Uiua::with_native_sys()
+            .with_mode(RunMode::Normal)
+            .with_args(args.clone())
+            .print_diagnostics(true)
+    };
+    let mut rt = new_rt();
+    let mut history: Vec<String> = Vec::new();
+    println!(
+        "Uiua {} (type :help for commands)",
+        env!("CARGO_PKG_VERSION")
+    );
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Failed to start the REPL: {e}");
+            return;
+        }
+    };
+    'inputs: loop {
+        // Read a possibly multi-line input
+        let mut input = String::new();
+        loop {
+            let prompt = if input.is_empty() { "   " } else { "...   " };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    input.push_str(&line);
+                    input.push('\n');
+                }
+                // Ctrl-C discards the current input
+                Err(ReadlineError::Interrupted) => continue 'inputs,
+                Err(_) if input.is_empty() => return,
+                Err(_) => break,
+            }
+            if !has_unclosed_brackets(&input) {
+                break;
+            }
+        }
+        let input = input.trim_end();
+        if input.trim().is_empty() {
+            continue;
+        }
+        _ = editor.add_history_entry(input);
+        // Handle commands
+        let code = if let Some(command) = input.trim().strip_prefix(':') {
+            let mut parts = command.split_whitespace();
+            match (parts.next().unwrap_or(""), parts.next()) {
+                ("help", None) => println!("{REPL_HELP}"),
+                ("clear", None) => _ = rt.take_stack(),
+                ("reset", None) => {
+                    let stack = rt.take_stack();
+                    rt = new_rt();
+                    for value in stack {
+                        rt.push(value);
+                    }
+                }
+                ("history", None) => {
+                    for (i, entry) in history.iter().enumerate() {
+                        for (j, line) in entry.lines().enumerate() {
+                            if j == 0 {
+                                println!("{:>4}  {line}", i + 1);
+                            } else {
+                                println!("      {line}");
+                            }
+                        }
+                    }
+                }
+                ("run", Some(n)) => match n.parse::<usize>() {
+                    Ok(n) if (1..=history.len()).contains(&n) => {
+                        let entry = history[n - 1].clone();
+                        println!("   {entry}");
+                        repl_line(&mut rt, &mut history, &config, entry);
+                    }
+                    _ => eprintln!("No history entry {n}"),
+                },
+                ("quit" | "exit" | "q", None) => return,
+                _ => eprintln!("Unknown command `:{command}`. Type :help for commands."),
+            }
+            continue;
+        } else {
+            input.to_string()
+        };
+        repl_line(&mut rt, &mut history, &config, code);
+    }
+}
+
+fn repl_line(rt: &mut Uiua, history: &mut Vec<String>, config: &FormatConfig, code: String) {
+    // Format the input so that names are turned into glyphs
+    let code = match format_str(&code, config) {
+        Ok(formatted) => {
+            let formatted = formatted.output.trim_end().to_string();
+            if formatted != code {
+                println!("   {}", formatted.replace('\n', "\n   ").bright_black());
+            }
+            formatted
+        }
+        Err(_) => code,
+    };
+    if history.last() != Some(&code) {
+        history.push(code.clone());
+    }
+    if let Err(e) = rt.load_str(&code) {
+        rt.recover();
+        println!("{}", e.show(true));
+    }
+    for value in rt.stack() {
+        println!("{}", value.show());
+    }
+}
+
+/// Check whether the input has brackets that have not been closed yet,
+/// ignoring brackets in strings, characters, and comments
+fn has_unclosed_brackets(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '@' => {
+                let escaped = chars.next() == Some('\\');
+                if escaped {
+                    chars.next();
+                }
+            }
+            '$' if chars.peek() == Some(&' ') => while chars.next_if(|&c| c != '\n').is_some() {},
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => _ = chars.next(),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+const DEBUG_HELP: &str = "\
+Debugger commands:
+  s, step          Execute one instruction, stepping into functions
+  n, next          Execute one word, stepping over functions
+  o, out           Run until the current function returns
+  c, continue      Run until the next breakpoint
+  st, stack        Show the stack
+  t, temp          Show the inline and under stacks
+  bt, backtrace    Show the call stack
+  b <bp>           Add a breakpoint at a line, line:column, or binding name
+  bl               List breakpoints
+  d <n>            Delete breakpoint <n>
+  q, quit          Stop execution
+  h, help          Show this message";
+
+fn parse_breakpoint(s: &str, input: &str, path: &Path) -> Option<Breakpoint> {
+    let (line, col) = match s.split_once(':') {
+        Some((line, col)) => (line, Some(col)),
+        None => (s, None),
+    };
+    if let Ok(line) = line.parse() {
+        let col = match col.map(str::parse).transpose() {
+            Ok(col) => col,
+            Err(_) => return None,
+        };
+        Breakpoint::at_line(input, Some(path), line, col)
+    } else if col.is_none() && !s.is_empty() && s.chars().all(|c| c.is_alphanumeric()) {
+        Some(Breakpoint::Binding(s.into()))
+    } else {
+        None
+    }
+}
+
+fn debug_prompt(
+    env: &Uiua,
+    reason: PauseReason,
+    breakpoints: &mut Breakpoints,
+    input: &str,
+    path: &Path,
+) -> DebugCommand {
+    let call_stack = env.call_stack();
+    let frame = call_stack.last();
+    // Show where execution is paused
+    if let PauseReason::Breakpoint(id) = reason {
+        if let Some(bp) = breakpoints.get(id) {
+            println!("{}", format!("Breakpoint {id}: {bp}").yellow());
+        }
+    }
+    match frame.and_then(|frame| frame.span.as_ref()) {
+        Some(Span::Code(span)) => {
+            let line = span.input.lines().nth(span.start.line - 1).unwrap_or("");
+            let width = if span.start.line == span.end.line {
+                span.end.col.saturating_sub(span.start.col).max(1)
+            } else {
+                1
+            };
+            println!("{} {}", format!("{span}").bright_black(), line);
+            println!(
+                "{}{}",
+                " ".repeat(span.to_string().chars().count() + span.start.col),
+                "^".repeat(width).bright_green()
+            );
+        }
+        _ => {
+            if let Some(frame) = frame {
+                println!("{}", format!("in {}", frame.id).bright_black());
+            }
+        }
+    }
+    if let Some(instr) = frame.and_then(|frame| frame.instr.as_ref()) {
+        println!("{} {instr}", "next:".bright_black());
+    }
+    let print_stack = |name: &str, stack: &[uiua::value::Value]| {
+        if stack.is_empty() {
+            println!("{}", format!("{name} is empty").bright_black());
+        }
+        for (i, value) in stack.iter().rev().enumerate() {
+            let shown = value.show();
+            for (j, line) in shown.lines().enumerate() {
+                if j == 0 {
+                    println!("{} {line}", format!("{name}[{i}]").bright_black());
+                } else {
+                    println!(
+                        "{} {line}",
+                        " ".repeat(name.len() + 2 + i.to_string().len())
+                    );
+                }
+            }
+        }
+    };
+    // Read commands
+    let stdin = io::stdin();
+    loop {
+        print!("{}", "(debug) ".bright_black());
+        _ = io::stdout().flush();
+        let mut line = String::new();
+        if stdin.read_line(&mut line).map_or(true, |n| n == 0) {
+            return DebugCommand::Abort;
+        }
+        let mut parts = line.split_whitespace();
+        match (parts.next().unwrap_or("s"), parts.next()) {
+            ("s" | "step", None) => return DebugCommand::StepInstr,
+            ("n" | "next", None) => return DebugCommand::StepWord,
+            ("o" | "out", None) => return DebugCommand::StepOut,
+            ("c" | "continue", None) => return DebugCommand::Continue,
+            ("q" | "quit", None) => return DebugCommand::Abort,
+            ("st" | "stack", None) => print_stack("stack", env.stack()),
+            ("t" | "temp", None) => {
+                print_stack("inline", env.inline_stack());
+                print_stack("under", env.under_stack());
+            }
+            ("bt" | "backtrace", None) => {
+                for (i, frame) in call_stack.iter().rev().enumerate() {
+                    let location = frame.span.as_ref().unwrap_or(&frame.call_span);
+                    println!("{i:>3} {} at {location}", frame.id);
+                }
+            }
+            ("b", Some(bp)) => match parse_breakpoint(bp, input, path) {
+                Some(bp) => {
+                    let text = bp.to_string();
+                    println!("Breakpoint {}: {text}", breakpoints.add(bp));
+                }
+                None => eprintln!("Invalid breakpoint `{bp}`"),
+            },
+            ("bl", None) => {
+                for (id, bp) in breakpoints.iter() {
+                    println!("{id:>3} {bp}");
+                }
+            }
+            ("d", Some(n)) => match n.parse::<usize>() {
+                Ok(id) if breakpoints.remove(id).is_some() => {}
+                _ => eprintln!("No breakpoint {n}"),
+            },
+            ("h" | "help", None) => println!("{DEBUG_HELP}"),
+            _ => eprintln!("Unknown command. Type `help` for commands."),
+        }
+    }
+}
+
 #[derive(Parser)]
 #[clap(version)]
 enum App {
@@ -429,6 +796,12 @@ enum App {
         #[cfg(feature = "audio")]
         #[clap(flatten)]
         audio_options: AudioOptions,
+        #[clap(flatten)]
+        sandbox_options: SandboxOptions,
+        #[clap(long, help = "Don't optimize compiled code")]
+        no_optimize: bool,
+        #[clap(long, help = "Don't run loops in parallel")]
+        no_parallel: bool,
         #[clap(trailing_var_arg = true)]
         args: Vec<String>,
     },
@@ -441,6 +814,29 @@ enum App {
         #[clap(trailing_var_arg = true)]
         args: Vec<String>,
     },
+    #[clap(about = "Run a file in the step debugger")]
+    Debug {
+        path: Option<PathBuf>,
+        #[clap(
+            long = "break",
+            short = 'b',
+            help = "Pause at a line, a line:column, or a binding name. \
+                    Without breakpoints, pause at the start"
+        )]
+        breakpoints: Vec<String>,
+        #[clap(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
+    #[clap(about = "Start an interactive session")]
+    Repl {
+        #[clap(flatten)]
+        formatter_options: FormatterOptions,
+        #[cfg(feature = "audio")]
+        #[clap(flatten)]
+        audio_options: AudioOptions,
+        #[clap(trailing_var_arg = true)]
+        args: Vec<String>,
+    },
     #[clap(about = "Format and test a file")]
     Test {
         path: Option<PathBuf>,
@@ -490,6 +886,73 @@ struct FormatterOptions {
     stdout: bool,
 }
 
+#[derive(clap::Args)]
+struct SandboxOptions {
+    #[clap(
+        long,
+        help = "Restrict system access. Files, commands, networking, \
+                and changing directories are denied unless allowed"
+    )]
+    sandbox: bool,
+    #[clap(
+        long,
+        requires = "sandbox",
+        help = "Allow reading files in a directory in the sandbox"
+    )]
+    allow_read: Vec<PathBuf>,
+    #[clap(
+        long,
+        requires = "sandbox",
+        help = "Allow writing files in a scratch directory in the sandbox"
+    )]
+    allow_write: Option<PathBuf>,
+    #[clap(
+        long,
+        requires = "sandbox",
+        help = "The maximum number of open files and threads in the sandbox"
+    )]
+    max_handles: Option<usize>,
+    #[clap(
+        long,
+        requires = "sandbox",
+        help = "The maximum number of bytes that can be read from files in the sandbox"
+    )]
+    max_read_bytes: Option<usize>,
+    #[clap(
+        long,
+        requires = "sandbox",
+        help = "The maximum number of bytes of array data that can be allocated in the sandbox"
+    )]
+    max_memory: Option<usize>,
+}
+
+impl SandboxOptions {
+    fn runtime(self) -> Uiua {
+        let max_memory = self.max_memory;
+        let rt = Uiua::with_backend(self.backend());
+        match max_memory {
+            Some(bytes) => rt.with_memory_limit(bytes),
+            None => rt,
+        }
+    }
+    fn backend(self) -> SandboxSys {
+        let mut backend = SandboxSys::default();
+        for dir in self.allow_read {
+            backend = backend.allow_read(dir);
+        }
+        if let Some(dir) = self.allow_write {
+            backend = backend.allow_write(dir);
+        }
+        if let Some(max) = self.max_handles {
+            backend = backend.with_max_handles(max);
+        }
+        if let Some(bytes) = self.max_read_bytes {
+            backend = backend.with_read_quota(bytes);
+        }
+        backend
+    }
+}
+
 #[cfg(feature = "audio")]
 #[derive(clap::Args)]
 struct AudioOptions {
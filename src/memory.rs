@@ -0,0 +1,193 @@
+//! Tracking of array memory for [`Uiua::with_memory_limit`](crate::Uiua::with_memory_limit)
+//!
+//! Array data is allocated through [`CowSlice`](crate::cowslice::CowSlice), which reports
+//! allocations and deallocations here. Usage is tracked in a [`Budget`], and only while a limit
+//! is set. A new budget starts out using the memory of the values the runtime already [`held`].
+//! Large allocations whose size is known ahead of time are checked with [`reserve`], which fails
+//! before anything is allocated. Other growth is only counted, and the runtime checks whether the
+//! limit was [`exceeded`] after each instruction.
+//!
+//! Budgets are stored per thread. Each thread has its own, except that the threads of
+//! a parallel loop [`share`] the budget of the thread that started the loop.
+//! Allocations made on a thread without a budget, such as by code that embeds the
+//! interpreter, are not tracked.
+
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::value::Value;
+
+/// A memory limit and the memory used toward it
+pub(crate) struct Budget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+thread_local! {
+    static BUDGET: RefCell<Option<Arc<Budget>>> = const { RefCell::new(None) };
+}
+
+/// The error returned when an allocation would exceed the memory limit
+#[derive(Debug)]
+pub(crate) struct MemoryLimitExceeded;
+
+/// Run a function with a memory limit for the current thread
+///
+/// If the thread already has a budget, it is used instead of a new one.
+/// Otherwise, the new budget starts with `held` bytes already used.
+pub(crate) fn limited<T>(limit: Option<usize>, held: usize, f: impl FnOnce() -> T) -> T {
+    let budget = current().or_else(|| {
+        limit.map(|limit| {
+            Arc::new(Budget {
+                limit,
+                used: AtomicUsize::new(held),
+            })
+        })
+    });
+    let Some(budget) = budget else {
+        return f();
+    };
+    let _shared = share(Some(budget));
+    f()
+}
+
+/// Get the budget of the current thread
+pub(crate) fn current() -> Option<Arc<Budget>> {
+    BUDGET.with(|budget| budget.borrow().clone())
+}
+
+/// Use a budget on the current thread until the returned guard is dropped
+pub(crate) fn share(budget: Option<Arc<Budget>>) -> impl Drop {
+    struct Restore(Option<Arc<Budget>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            BUDGET.with(|budget| *budget.borrow_mut() = self.0.take());
+        }
+    }
+    Restore(BUDGET.with(|prev| prev.replace(budget)))
+}
+
+/// Check that an allocation of some number of bytes would not exceed the limit
+///
+/// This should be called before large allocations whose size is known ahead of time.
+pub(crate) fn reserve(bytes: usize) -> Result<(), MemoryLimitExceeded> {
+    BUDGET.with(|budget| match &*budget.borrow() {
+        Some(budget)
+            if budget.used.load(Ordering::Relaxed).saturating_add(bytes) > budget.limit =>
+        {
+            Err(MemoryLimitExceeded)
+        }
+        _ => Ok(()),
+    })
+}
+
+/// Check whether the memory used on the current thread is over the limit
+pub(crate) fn exceeded() -> bool {
+    BUDGET.with(|budget| match &*budget.borrow() {
+        Some(budget) => budget.used.load(Ordering::Relaxed) > budget.limit,
+        None => false,
+    })
+}
+
+/// Get the number of bytes of array data held by some values
+///
+/// Data shared between values is only counted once.
+pub(crate) fn held<'a>(values: impl IntoIterator<Item = &'a Value>) -> usize {
+    let mut seen = HashSet::new();
+    (values.into_iter())
+        .map(|value| match value {
+            Value::Num(arr) => arr.data.allocation(),
+            Value::Byte(arr) => arr.data.allocation(),
+            Value::Char(arr) => arr.data.allocation(),
+            Value::Func(arr) => arr.data.allocation(),
+        })
+        .filter(|&(addr, _)| seen.insert(addr))
+        .map(|(_, bytes)| bytes)
+        .sum()
+}
+
+/// Record an allocation
+pub(crate) fn track(bytes: usize) {
+    if bytes == 0 {
+        return;
+    }
+    BUDGET.with(|budget| {
+        if let Some(budget) = &*budget.borrow() {
+            budget.used.fetch_add(bytes, Ordering::Relaxed);
+        }
+    })
+}
+
+/// Record a deallocation
+pub(crate) fn release(bytes: usize) {
+    if bytes == 0 {
+        return;
+    }
+    BUDGET.with(|budget| {
+        if let Some(budget) = &*budget.borrow() {
+            _ = (budget.used).fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Uiua;
+
+    #[test]
+    fn memory_limit() {
+        let mut env = Uiua::with_native_sys().with_memory_limit(1 << 20);
+        env.load_str("/+ ⇡ 1000").unwrap();
+        for code in [
+            "↯ 1000000000 1",
+            "⊞+ . ⇡ 100000",
+            "⍥(⊂.)30 [1]",
+            "⇡ [100 100 100]",
+            "▽ 1000000000 1",
+            "▽ 1000000000 [1 2]",
+            "⬚0↙ 1000000000 [1 2]",
+            "↯ 100000000000000000000 [1 2]",
+        ] {
+            match env.load_str(code) {
+                Err(e) if e.message() == "Maximum memory usage exceeded" => {}
+                res => panic!("Expected memory limit error for {code}, but got {res:?}"),
+            }
+            // Values made by the instruction that went over the limit are left on the stack
+            env.take_stack();
+        }
+        env.load_str("/+ ⇡ 1000").unwrap();
+        // Values kept from earlier loads count toward the limit
+        env.load_str("⇡ 100000").unwrap();
+        match env.load_str("⇡ 100000") {
+            Err(e) if e.message() == "Maximum memory usage exceeded" => {}
+            res => panic!("Expected memory limit error, but got {res:?}"),
+        }
+        env.take_stack();
+        env.load_str("⇡ 100000").unwrap();
+    }
+
+    #[test]
+    fn parallel_memory_limit() {
+        // Use several threads even if the machine only has one core
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            let mut env = Uiua::with_native_sys().with_memory_limit(1 << 20);
+            match env.load_str("≡(/+↯ 200000) ⇡20000") {
+                Err(e) if e.to_string().contains("Maximum memory usage exceeded") => {}
+                res => panic!("Expected memory limit error, but got {res:?}"),
+            }
+            env.load_str("≡(/+↯ 10) ⇡20000").unwrap();
+        });
+    }
+}
@@ -0,0 +1,262 @@
+//! An optimization pass over compiled [`Instr`]s
+//!
+//! Pure primitives whose arguments are all constants are folded into a single push,
+//! stack operations that cancel each other out are removed, and chains of pervasive
+//! operations are fused so that they run in a single loop.
+//!
+//! Optimizations never change the signature of the instructions they are applied to.
+//! Instructions that remain keep their spans, and anything that would fail is left
+//! as-is so that the error is reported at runtime in the usual way.
+
+use std::{mem::take, sync::Arc};
+
+use crate::{
+    algorithm::pervade::{fusable_tail, Fused},
+    check::instrs_signature,
+    function::Instr,
+    primitive::{PrimClass, Primitive},
+    Uiua,
+};
+
+impl Uiua {
+    /// Optimize a list of compiled instructions
+    pub(crate) fn optimize_instrs(&mut self, instrs: Vec<Instr>) -> Vec<Instr> {
+        // Optimizing would merge or remove instructions that the debugger can pause on
+        if !self.optimize || self.debugger.is_some() {
+            return instrs;
+        }
+        let mut optimized = Vec::with_capacity(instrs.len());
+        for instr in instrs {
+            optimized.push(instr);
+            while self.optimize_tail(&mut optimized) {}
+        }
+        optimized
+    }
+    /// Apply a single optimization to the end of a list of instructions
+    ///
+    /// Returns whether the instructions changed
+    fn optimize_tail(&mut self, instrs: &mut Vec<Instr>) -> bool {
+        use Primitive::*;
+        match instrs.as_slice() {
+            // Pushing a value only to pop it
+            [.., Instr::Push(_), Instr::Prim(Pop, _)] => {
+                instrs.truncate(instrs.len() - 2);
+                true
+            }
+            // Shuffles that undo each other
+            [.., Instr::Prim(Flip, _), Instr::Prim(Flip, _)] => remove_last_pair(instrs, 2),
+            [.., Instr::Prim(Dup, _), Instr::Prim(Pop, _)] => remove_last_pair(instrs, 1),
+            // Temporary values that are put back right away
+            [.., Instr::PushTemp {
+                count: a,
+                kind: a_kind,
+                ..
+            }, Instr::PopTemp {
+                count: b,
+                kind: b_kind,
+                ..
+            }] if a == b && a_kind == b_kind => {
+                let count = *a;
+                remove_last_pair(instrs, count)
+            }
+            [.., Instr::PushTemp { count: 0, .. }
+            | Instr::PopTemp { count: 0, .. }
+            | Instr::CopyTemp { count: 0, .. }
+            | Instr::DropTemp { count: 0, .. }] => {
+                instrs.pop();
+                true
+            }
+            [.., Instr::Prim(prim, _)] if is_foldable(*prim) => {
+                self.fold_constants(instrs) || self.fuse_tail(instrs)
+            }
+            _ => self.fuse_tail(instrs),
+        }
+    }
+    /// Fuse a pervasive operation at the end of the instructions with the ones before it
+    fn fuse_tail(&self, instrs: &mut Vec<Instr>) -> bool {
+        // Every instruction is kept when debugging so that each one can be paused on
+        if self.debugger.is_some() {
+            return false;
+        }
+        let Some((step, len)) = fusable_tail(instrs) else {
+            return false;
+        };
+        let split = instrs.len() - len;
+        if let Some(Instr::Fused(_)) = instrs[..split].last() {
+            let tail: Vec<Instr> = instrs.drain(split..).collect();
+            let Some(Instr::Fused(fused)) = instrs.last_mut() else {
+                unreachable!()
+            };
+            let fused = Arc::make_mut(fused);
+            fused.steps.push(step);
+            fused.instrs.extend(tail);
+            return true;
+        }
+        let Some((prev, prev_len)) = fusable_tail(&instrs[..split]) else {
+            return false;
+        };
+        let instrs_to_fuse: Vec<Instr> = instrs.drain(split - prev_len..).collect();
+        instrs.push(Instr::Fused(Arc::new(Fused {
+            steps: vec![prev, step],
+            instrs: instrs_to_fuse,
+        })));
+        true
+    }
+    /// Replace a primitive whose arguments are all pushed constants with its outputs
+    fn fold_constants(&mut self, instrs: &mut Vec<Instr>) -> bool {
+        let Some(&Instr::Prim(prim, _)) = instrs.last() else {
+            return false;
+        };
+        let Some(args) = prim.args() else {
+            return false;
+        };
+        let Some(start) = instrs.len().checked_sub(args as usize + 1) else {
+            return false;
+        };
+        let args = &instrs[start..instrs.len() - 1];
+        if !args.iter().all(|instr| matches!(instr, Instr::Push(_))) {
+            return false;
+        }
+        // Folding ignores fill values, so constants are not folded in fill functions
+        if self.fill_depth > 0 {
+            return false;
+        }
+        // The primitive is run on an empty stack in a fresh scope,
+        // so that neither the current stack nor any fill values affect the result
+        let stack = take(&mut self.stack);
+        let scope = take(&mut self.scope);
+        self.stack
+            .extend(args.iter().filter_map(Instr::as_push).cloned());
+        let res = prim.run(self);
+        let outputs = take(&mut self.stack);
+        self.stack = stack;
+        self.scope = scope;
+        if res.is_err() {
+            return false;
+        }
+        instrs.truncate(start);
+        instrs.extend(outputs.into_iter().map(Instr::push));
+        true
+    }
+}
+
+/// Remove the last two instructions if they cancel out
+///
+/// They are only removed if the values they use are produced by the instructions
+/// before them, so that removing them does not change the signature.
+fn remove_last_pair(instrs: &mut Vec<Instr>, depth: usize) -> bool {
+    let before = &instrs[..instrs.len() - 2];
+    if instrs_signature(before).is_ok_and(|sig| sig.outputs >= depth) {
+        instrs.truncate(instrs.len() - 2);
+        true
+    } else {
+        false
+    }
+}
+
+/// Whether a primitive is pure and has outputs no bigger than its biggest argument
+fn is_foldable(prim: Primitive) -> bool {
+    use Primitive::*;
+    matches!(
+        prim.class(),
+        PrimClass::Constant | PrimClass::MonadicPervasive | PrimClass::DyadicPervasive
+    ) || matches!(
+        prim,
+        Len | Shape
+            | First
+            | Last
+            | Reverse
+            | Deshape
+            | Transpose
+            | Rise
+            | Fall
+            | Classify
+            | Deduplicate
+            | Match
+            | Rotate
+            | Member
+            | IndexOf
+            | Find
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ast::Item, function::Instr, parse::parse, primitive::Primitive, value::Value, Uiua,
+    };
+
+    fn compile(env: &mut Uiua, code: &str) -> Vec<Instr> {
+        let (items, errors, _) = parse(code, None);
+        assert!(errors.is_empty(), "{errors:?}");
+        match items.into_iter().next() {
+            Some(Item::Words(words)) => env.compile_words(words, true).unwrap(),
+            item => panic!("Expected words, but got {item:?}"),
+        }
+    }
+
+    #[test]
+    fn optimize() {
+        let mut env = Uiua::with_native_sys();
+        assert_eq!(compile(&mut env, "+1 ×2 3"), [Instr::push(7.0)]);
+        assert_eq!(
+            compile(&mut env, "⇌ ↻ 1 [1 2 3]"),
+            [Instr::push(vec![1.0, 3.0, 2.0])]
+        );
+        assert_eq!(
+            compile(&mut env, "∶∶ 1 2"),
+            [Instr::push(2.0), Instr::push(1.0)]
+        );
+        assert_eq!(compile(&mut env, ";. 1"), [Instr::push(1.0)]);
+        assert_eq!(compile(&mut env, "; 1 2"), [Instr::push(2.0)]);
+        assert_eq!(
+            compile(&mut env, "⊙(; 3) 1 2"),
+            [Instr::push(2.0), Instr::push(1.0)]
+        );
+        // Shuffles of values from outside are kept so that signatures do not change
+        assert_eq!(compile(&mut env, "∶∶ 1").len(), 3);
+        assert_eq!(compile(&mut env, ";.").len(), 2);
+        // Impure and failing primitives are not folded
+        assert!(matches!(
+            compile(&mut env, "+ 1 ⚂").as_slice(),
+            [
+                Instr::Prim(Primitive::Rand, _),
+                _,
+                Instr::Prim(Primitive::Add, _)
+            ]
+        ));
+        let instrs = compile(&mut env, "+ [1 2] [1 2 3]");
+        assert!(matches!(
+            instrs.last(),
+            Some(Instr::Prim(Primitive::Add, _))
+        ));
+        let err = env.load_str("+ [1 2] [1 2 3]").unwrap_err();
+        assert!(err.to_string().contains("1:1"), "{err}");
+        // Nor are primitives in fill functions
+        let instrs = compile(&mut env, "⬚0(+ [1 2] [3])");
+        let Some(Instr::Push(f)) = instrs.first() else {
+            panic!("Expected the fill function first, but got {instrs:?}");
+        };
+        assert!(matches!(
+            f.as_function().map(|f| f.instrs.last()),
+            Some(Some(Instr::Prim(Primitive::Add, _)))
+        ));
+        env.load_str("⬚0(+ [1 2] [3])").unwrap();
+        assert_eq!(env.pop(1).unwrap(), Value::from(vec![4.0, 2.0]));
+
+        // Chains of pervasive operations are fused
+        assert!(matches!(
+            compile(&mut env, "×2 +1 ÷2").as_slice(),
+            [Instr::Fused(_)]
+        ));
+        assert!(matches!(
+            compile(&mut env, "⇌ ×2 +1 ¯").as_slice(),
+            [Instr::Fused(_), Instr::Prim(Primitive::Reverse, _)]
+        ));
+
+        let mut env = Uiua::with_native_sys().with_optimizations(false);
+        assert_eq!(compile(&mut env, "+1 ×2 3").len(), 5);
+        assert_eq!(compile(&mut env, "×2 +1").len(), 4);
+        assert_eq!(compile(&mut env, "∶∶ 1 2").len(), 4);
+    }
+}
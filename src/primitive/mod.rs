@@ -160,6 +160,27 @@ impl Primitive {
     pub fn is_modifier(&self) -> bool {
         self.modifier_args().is_some()
     }
+    /// Describe the signatures that a modifier expects its operands to have
+    ///
+    /// There is one description for each operand, in the order they are written.
+    pub fn operand_signatures(&self) -> Option<&'static [&'static str]> {
+        use Primitive::*;
+        Some(match self {
+            Reduce | Scan | Table | Cross => &["|2.1"],
+            Fold => &["one more argument than outputs"],
+            Each | Rows | Distribute | Both | Dip | Gap | Spawn | Dump => &["any"],
+            Repeat => &["|1.1, or any if the count is a constant"],
+            Group | Partition => &["at least 1 output"],
+            Bracket | Fork | Bind => &["any", "any"],
+            Invert => &["invertible"],
+            Under => &["invertible", "any"],
+            Level => &["ranks", "any"],
+            Fill => &["fill value", "any"],
+            If => &["any", "same outputs as the first"],
+            Try => &["any", "one more argument than the first"],
+            _ => return None,
+        })
+    }
     pub(crate) fn deprecation_suggestion(&self) -> Option<String> {
         match self {
             Primitive::Roll | Primitive::Unroll => {
@@ -797,7 +818,7 @@ impl PrimExample {
         self.should_error
     }
     pub fn should_run(&self) -> bool {
-        !["&sl", "&tcpc"]
+        !["&sl", "&tcpc", "&ffi"]
             .iter()
             .any(|prim| self.input.contains(prim))
     }
@@ -1016,6 +1037,14 @@ mod tests {
         assert_eq!(Primitive::from_format_name_multi("foo"), None);
     }
 
+    #[test]
+    fn operand_signatures() {
+        for prim in Primitive::all() {
+            let operands = prim.operand_signatures().map(|sigs| sigs.len() as u8);
+            assert_eq!(operands, prim.modifier_args(), "{prim:?}");
+        }
+    }
+
     #[cfg(test)]
     #[test]
     fn gen_grammar_file() {
@@ -1,7 +1,7 @@
 use std::{
-    collections::{BTreeSet, HashMap, HashSet},
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap, HashSet},
     fs,
-    hash::Hash,
+    hash::{Hash, Hasher},
     mem::take,
     panic::{catch_unwind, AssertUnwindSafe},
     path::{Path, PathBuf},
@@ -11,11 +11,15 @@ use std::{
 
 use instant::Duration;
 use parking_lot::Mutex;
+use rayon::prelude::*;
 
 use crate::{
+    algorithm::{loops::lazy_windows, pervade::run_fused},
     array::Array,
+    debug::{CallFrame, Debugger},
     function::*,
-    lex::Span,
+    lex::{is_ident_char, Span},
+    memory,
     parse::parse,
     primitive::{Primitive, CONSTANTS},
     value::Value,
@@ -23,11 +27,19 @@ use crate::{
     UiuaResult,
 };
 
+/// The minimum estimated work for a loop to run its function in parallel
+///
+/// This is the number of calls or elements, whichever is larger, times the number of
+/// instructions in the function.
+const PARALLEL_MIN_WORK: usize = 1 << 14;
+
 /// The Uiua runtime
 #[derive(Clone)]
 pub struct Uiua {
     /// Functions which are under construction
     pub(crate) new_functions: Vec<Vec<Instr>>,
+    /// The number of fill modifiers whose functions are being compiled
+    pub(crate) fill_depth: usize,
     /// Global values
     pub(crate) globals: Arc<Mutex<Vec<Value>>>,
     /// Indexable spans
@@ -48,12 +60,21 @@ pub struct Uiua {
     execution_limit: Option<f64>,
     /// The time at which execution started
     execution_start: f64,
+    /// A limit on the bytes of array data allocated during execution
+    memory_limit: Option<usize>,
+    /// Whether to optimize compiled instructions
+    pub(crate) optimize: bool,
+    /// Whether to run pure functions in loops in parallel
+    parallel: bool,
     /// The paths of files currently being imported (used to detect import cycles)
     current_imports: Arc<Mutex<HashSet<PathBuf>>>,
     /// The stacks of imported files
     imports: Arc<Mutex<HashMap<PathBuf, Vec<Value>>>>,
     /// Accumulated diagnostics
     pub(crate) diagnostics: BTreeSet<Diagnostic>,
+    /// Signatures inferred while compiling, if they are being recorded
+    #[cfg(feature = "lsp")]
+    pub(crate) inferred_signatures: Option<Vec<InferredSignature>>,
     /// Print diagnostics as they are encountered
     pub(crate) print_diagnostics: bool,
     /// Arguments passed from the command line
@@ -62,6 +83,10 @@ pub struct Uiua {
     cli_file_path: PathBuf,
     /// The system backend
     pub(crate) backend: Arc<dyn SysBackend>,
+    /// The attached debugger
+    pub(crate) debugger: Option<Arc<Mutex<Debugger>>>,
+    /// Rust functions bound as Uiua bindings
+    native_bindings: Vec<NativeBinding>,
 }
 
 #[derive(Clone)]
@@ -168,16 +193,24 @@ impl Uiua {
             higher_scopes: Vec::new(),
             globals: Arc::new(Mutex::new(globals)),
             new_functions: Vec::new(),
+            fill_depth: 0,
             current_imports: Arc::new(Mutex::new(HashSet::new())),
             imports: Arc::new(Mutex::new(HashMap::new())),
             mode: RunMode::Normal,
             diagnostics: BTreeSet::new(),
+            #[cfg(feature = "lsp")]
+            inferred_signatures: None,
             backend: Arc::new(NativeSys),
             print_diagnostics: false,
             cli_arguments: Vec::new(),
             cli_file_path: PathBuf::new(),
             execution_limit: None,
             execution_start: 0.0,
+            memory_limit: None,
+            optimize: true,
+            parallel: true,
+            debugger: None,
+            native_bindings: Vec::new(),
         }
     }
     /// Create a new Uiua runtime with a custom IO backend
@@ -202,6 +235,44 @@ impl Uiua {
         self.execution_limit = Some(limit.as_millis() as f64);
         self
     }
+    /// Limit the number of bytes of array data that can be allocated during execution
+    ///
+    /// The limit applies to each load and to each spawned thread.
+    /// Array data already on the stack or in bindings when a load starts counts toward it.
+    /// The threads of a parallel loop share the limit of the thread that runs the loop.
+    /// Exceeding it causes a [`UiuaError::MemoryLimit`] error.
+    ///
+    /// Large allocations are checked before they are made.
+    /// Other growth is only noticed after the instruction that caused it.
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+    /// Set whether to optimize compiled code
+    ///
+    /// Optimization is on by default. Turning it off can help when debugging the compiler.
+    /// It is always off while a [`Debugger`] is attached.
+    pub fn with_optimizations(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+    /// Set whether to run loops in parallel
+    ///
+    /// When on, which is the default, [`Primitive::Rows`] and [`Primitive::Each`] with
+    /// a single array call pure functions on its rows or elements in parallel.
+    /// Results and errors are the same either way.
+    pub fn with_parallel_loops(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+    /// Attach a [`Debugger`]
+    ///
+    /// Spawned threads are not debugged.
+    /// Code compiled while a debugger is attached is not optimized.
+    pub fn with_debugger(mut self, debugger: Debugger) -> Self {
+        self.debugger = Some(Arc::new(Mutex::new(debugger)));
+        self
+    }
     /// Set the [`RunMode`]
     ///
     /// Default is [`RunMode::Normal`]
@@ -232,6 +303,10 @@ impl Uiua {
         self.cli_file_path.as_path()
     }
     /// Load a Uiua file from a path
+    ///
+    /// Files, including imported ones, are parsed and compiled on every load.
+    /// Compiled output is not cached: instructions refer to bindings and spans by their
+    /// index in this runtime, and bindings are evaluated while their file is compiled.
     pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> UiuaResult {
         let path = path.as_ref();
         let input = fs::read_to_string(path).map_err(|e| UiuaError::Load(path.into(), e.into()))?;
@@ -280,7 +355,9 @@ impl Uiua {
         if let Some(path) = path {
             self.current_imports.lock().insert(path.into());
         }
-        let res = match catch_unwind(AssertUnwindSafe(|| self.items(items, false))) {
+        let res = match catch_unwind(AssertUnwindSafe(|| {
+            self.limit_memory(|env| env.items(items, false))
+        })) {
             Ok(res) => res,
             Err(_) => Err(self.error(format!(
                 "\
@@ -300,6 +377,40 @@ code:
         }
         res
     }
+    /// Get the number of bytes of array data held by the runtime
+    fn held_memory(&self) -> usize {
+        let globals = self.globals.lock();
+        let imports = self.imports.lock();
+        memory::held(
+            (self.stack.iter())
+                .chain(&self.inline_stack)
+                .chain(&self.under_stack)
+                .chain(globals.iter())
+                .chain(imports.values().flatten()),
+        )
+    }
+    /// Run a function with the memory limit
+    fn limit_memory<T>(&mut self, f: impl FnOnce(&mut Self) -> UiuaResult<T>) -> UiuaResult<T> {
+        let limit = self.memory_limit;
+        let held = if limit.is_some() {
+            self.held_memory()
+        } else {
+            0
+        };
+        memory::limited(limit, held, || f(self))
+    }
+    /// Check that an allocation of some number of bytes would not exceed the memory limit
+    pub(crate) fn reserve(&self, bytes: usize) -> UiuaResult {
+        memory::reserve(bytes).map_err(|_| UiuaError::MemoryLimit(self.span()))
+    }
+    /// Trace an error through the call frames above a height
+    fn trace_frames(&mut self, mut error: UiuaError, height: usize) -> UiuaError {
+        let frames = self.scope.call.split_off(height.min(self.scope.call.len()));
+        for frame in frames {
+            error = self.trace_error(error, frame);
+        }
+        error
+    }
     fn trace_error(&self, mut error: UiuaError, frame: StackFrame) -> UiuaError {
         let mut frames = Vec::new();
         for (span, prim) in &frame.spans {
@@ -352,10 +463,36 @@ code:
         self.scope.call.push(frame);
         while self.scope.call.len() > ret_height {
             let frame = self.scope.call.last().unwrap();
-            let Some(instr) = frame.function.instrs.get(frame.pc) else {
+            if frame.pc >= frame.function.instrs.len() {
                 self.scope.call.pop();
                 continue;
-            };
+            }
+            if let Some(debugger) = self.debugger.clone() {
+                if let Err(err) = self.debug_check(&debugger) {
+                    self.scope.call.truncate(ret_height);
+                    return Err(err);
+                }
+            }
+            // Run rows and reduce over windows without materializing the windows
+            let frame = self.scope.call.last().unwrap();
+            if self.debugger.is_none()
+                && matches!(
+                    frame.function.instrs[frame.pc],
+                    Instr::Prim(Primitive::Windows, _)
+                )
+            {
+                let function = frame.function.clone();
+                let pc = frame.pc;
+                if let Some(res) = lazy_windows(&function.instrs[pc..], self) {
+                    if let Err(err) = res {
+                        return Err(self.trace_frames(err, ret_height));
+                    }
+                    self.scope.call.last_mut().unwrap().pc += 3;
+                    continue;
+                }
+            }
+            let frame = self.scope.call.last().unwrap();
+            let instr = &frame.function.instrs[frame.pc];
             // Uncomment to debug
             // if !self.scope.array.is_empty() {
             //     print!("array: ");
@@ -481,17 +618,18 @@ code:
                     self.pop_span();
                     Ok(())
                 })(),
-            };
-            if let Err(mut err) = res {
-                // Trace errors
-                let frames = self
-                    .scope
-                    .call
-                    .split_off(ret_height.min(self.scope.call.len()));
-                for frame in frames {
-                    err = self.trace_error(err, frame);
+                Instr::Fused(fused) => {
+                    let fused = fused.clone();
+                    if let Some(Value::Num(arr)) = self.stack.last_mut() {
+                        run_fused(&fused.steps, &mut arr.data);
+                        Ok(())
+                    } else {
+                        self.run_unfused(&fused.instrs)
+                    }
                 }
-                return Err(err);
+            };
+            if let Err(err) = res {
+                return Err(self.trace_frames(err, ret_height));
             } else {
                 // Go to next instruction
                 self.scope.call.last_mut().unwrap().pc += 1;
@@ -500,10 +638,68 @@ code:
                         return Err(UiuaError::Timeout(self.span()));
                     }
                 }
+                if self.memory_limit.is_some() && memory::exceeded() {
+                    let error = UiuaError::MemoryLimit(self.span());
+                    return Err(self.trace_frames(error, ret_height));
+                }
             }
         }
         Ok(())
     }
+    /// Run the instructions of a fused chain one at a time
+    fn run_unfused(&mut self, instrs: &[Instr]) -> UiuaResult {
+        for instr in instrs {
+            match instr {
+                Instr::Push(val) => self.stack.push(Value::clone(val)),
+                &Instr::Prim(prim, span) => {
+                    self.push_span(span, Some(prim));
+                    prim.run(self)?;
+                    self.pop_span();
+                }
+                instr => unreachable!("{instr} cannot be fused"),
+            }
+        }
+        Ok(())
+    }
+    fn debug_check(&mut self, debugger: &Mutex<Debugger>) -> UiuaResult {
+        let frame = self.scope.call.last().unwrap();
+        let span = (frame.function.instrs[frame.pc].span()).map(|i| self.spans.lock()[i].clone());
+        let entered = (frame.pc == 0).then(|| frame.function.id.clone());
+        let depth = self.call_depth();
+        let mut debugger = debugger.lock();
+        if let Some(reason) = debugger.check(depth, span.as_ref(), entered.as_ref()) {
+            debugger.pause(self, reason, depth)?;
+        }
+        Ok(())
+    }
+    fn call_depth(&self) -> usize {
+        (self.higher_scopes.iter())
+            .chain([&self.scope])
+            .map(|scope| scope.call.len())
+            .sum()
+    }
+    /// Get the call stack, from the outermost function to the innermost one
+    pub fn call_stack(&self) -> Vec<CallFrame> {
+        let spans = self.spans.lock();
+        (self.higher_scopes.iter())
+            .chain([&self.scope])
+            // The first frame of each scope is a placeholder
+            .flat_map(|scope| scope.call.iter().skip(1))
+            .map(|frame| {
+                let instr = frame.function.instrs.get(frame.pc).cloned();
+                CallFrame {
+                    id: frame.function.id.clone(),
+                    call_span: spans[frame.call_span].clone(),
+                    pc: frame.pc,
+                    span: instr
+                        .as_ref()
+                        .and_then(Instr::span)
+                        .map(|i| spans[i].clone()),
+                    instr,
+                }
+            })
+            .collect()
+    }
     pub(crate) fn push_span(&mut self, span: usize, prim: Option<Primitive>) {
         self.scope.call.last_mut().unwrap().spans.push((span, prim));
     }
@@ -630,6 +826,37 @@ code:
     pub fn take_stack(&mut self) -> Vec<Value> {
         take(&mut self.stack)
     }
+    /// Get a reference to the stack
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+    /// Get a reference to the temp stack for inlining
+    pub fn inline_stack(&self) -> &[Value] {
+        &self.inline_stack
+    }
+    /// Get a reference to the temp stack for unders
+    pub fn under_stack(&self) -> &[Value] {
+        &self.under_stack
+    }
+    /// Restore a consistent state after an error
+    ///
+    /// Bindings and the stack are kept, but any partially built
+    /// functions, arrays, scopes, and temporary values are discarded.
+    pub fn recover(&mut self) {
+        if !self.higher_scopes.is_empty() {
+            self.scope = self.higher_scopes.swap_remove(0);
+            self.higher_scopes.clear();
+        }
+        self.scope.array.clear();
+        self.scope.call.truncate(1);
+        if let Some(frame) = self.scope.call.first_mut() {
+            frame.spans.clear();
+        }
+        self.scope.fills = Fills::default();
+        self.new_functions.clear();
+        self.inline_stack.clear();
+        self.under_stack.clear();
+    }
     /// Get the values for all bindings in the current scope
     pub fn all_bindings_in_scope(&self) -> HashMap<Ident, Value> {
         let mut bindings = HashMap::new();
@@ -641,6 +868,117 @@ code:
         }
         bindings
     }
+    /// Call a binding by name with the given arguments
+    ///
+    /// The first argument ends up on the top of the stack.
+    /// The outputs are returned with the top of the stack first.
+    /// The stack is left as it was before the call.
+    pub fn call_binding(
+        &mut self,
+        name: &str,
+        args: impl IntoIterator<Item = Value>,
+    ) -> UiuaResult<Vec<Value>> {
+        let Some(&idx) = self.scope.names.get(name) else {
+            return Err(UiuaError::Run(
+                Span::Builtin.sp(format!("No binding named `{name}`")),
+            ));
+        };
+        let value = self.globals.lock()[idx].clone();
+        let sig = value.signature();
+        let args: Vec<Value> = args.into_iter().collect();
+        if args.len() != sig.args {
+            return Err(UiuaError::Run(Span::Builtin.sp(format!(
+                "`{name}` expects {} argument{}, but {} {} given",
+                sig.args,
+                if sig.args == 1 { "" } else { "s" },
+                args.len(),
+                if args.len() == 1 { "was" } else { "were" }
+            ))));
+        }
+        let caller_stack = take(&mut self.stack);
+        self.stack.extend(args.into_iter().rev());
+        let res = self.limit_memory(|env| env.call(value));
+        let mut outputs = take(&mut self.stack);
+        self.stack = caller_stack;
+        if let Err(e) = res {
+            self.recover();
+            return Err(e);
+        }
+        if outputs.len() != sig.outputs {
+            return Err(UiuaError::Run(Span::Builtin.sp(format!(
+                "`{name}` was expected to return {} value{}, but it returned {}",
+                sig.outputs,
+                if sig.outputs == 1 { "" } else { "s" },
+                outputs.len()
+            ))));
+        }
+        outputs.reverse();
+        Ok(outputs)
+    }
+    /// Bind a Rust function to a name that Uiua code can call
+    ///
+    /// The function pops its arguments from the stack and pushes its outputs,
+    /// which must match the given signature.
+    /// The documentation is shown when hovering over the name in the language server.
+    ///
+    /// Code loaded after this call can use the binding.
+    ///
+    /// ```
+    /// use uiua::{function::Signature, FromValue, Uiua};
+    ///
+    /// let mut env = Uiua::with_native_sys();
+    /// env.bind_native("Double", Signature::new(1, 1), "Double a number", |env| {
+    ///     let n = f64::from_value(env.pop(1)?).map_err(|e| env.error(e))?;
+    ///     env.push(n * 2.0);
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// env.load_str("Double 21").unwrap();
+    /// assert_eq!(f64::from_value(env.pop(1).unwrap()), Ok(42.0));
+    /// ```
+    pub fn bind_native(
+        &mut self,
+        name: impl Into<Ident>,
+        signature: Signature,
+        doc: impl Into<String>,
+        f: impl Fn(&mut Uiua) -> UiuaResult + Send + Sync + 'static,
+    ) -> UiuaResult {
+        let name = name.into();
+        if name.is_empty() || !name.chars().all(is_ident_char) {
+            return Err(UiuaError::Run(
+                Span::Builtin.sp(format!("`{name}` is not a valid binding name")),
+            ));
+        }
+        let id = {
+            let mut hasher = DefaultHasher::new();
+            name.hash(&mut hasher);
+            hasher.finish()
+        };
+        let f = Function::new(
+            FunctionId::Named(name.clone()),
+            vec![Instr::Dynamic(DynamicFunction {
+                id,
+                f: Arc::new(f),
+                signature,
+            })],
+            signature,
+        );
+        let mut globals = self.globals.lock();
+        let idx = globals.len();
+        globals.push(f.into());
+        self.scope.names.insert(name.clone(), idx);
+        self.native_bindings.retain(|binding| binding.name != name);
+        self.native_bindings.push(NativeBinding {
+            name,
+            signature,
+            doc: doc.into(),
+        });
+        Ok(())
+    }
+    /// Get the Rust functions bound with [`Uiua::bind_native`]
+    pub fn native_bindings(&self) -> &[NativeBinding] {
+        &self.native_bindings
+    }
     pub fn diagnostics(&self) -> &BTreeSet<Diagnostic> {
         &self.diagnostics
     }
@@ -785,27 +1123,14 @@ code:
         }
         res
     }
-    /// Spawn a thread
-    pub(crate) fn spawn(
-        &mut self,
-        capture_count: usize,
-        f: impl FnOnce(&mut Self) -> UiuaResult + Send + 'static,
-    ) -> UiuaResult<Value> {
-        if self.stack.len() < capture_count {
-            return Err(self.error(format!(
-                "Excepted at least {} value(s) on the stack, but there are {}",
-                capture_count,
-                self.stack.len()
-            )))?;
-        }
-        let env = Uiua {
+    /// Create an environment to run code on another thread
+    fn thread_env(&self, stack: Vec<Value>) -> Self {
+        Uiua {
             new_functions: Vec::new(),
+            fill_depth: 0,
             globals: self.globals.clone(),
             spans: self.spans.clone(),
-            stack: self
-                .stack
-                .drain(self.stack.len() - capture_count..)
-                .collect(),
+            stack,
             inline_stack: Vec::new(),
             under_stack: Vec::new(),
             scope: self.scope.clone(),
@@ -814,15 +1139,41 @@ code:
             current_imports: self.current_imports.clone(),
             imports: self.imports.clone(),
             diagnostics: BTreeSet::new(),
+            #[cfg(feature = "lsp")]
+            inferred_signatures: None,
             print_diagnostics: self.print_diagnostics,
             cli_arguments: self.cli_arguments.clone(),
             cli_file_path: self.cli_file_path.clone(),
             backend: self.backend.clone(),
             execution_limit: self.execution_limit,
             execution_start: self.execution_start,
-        };
+            memory_limit: self.memory_limit,
+            optimize: self.optimize,
+            parallel: self.parallel,
+            debugger: None,
+            native_bindings: self.native_bindings.clone(),
+        }
+    }
+    /// Spawn a thread
+    pub(crate) fn spawn(
+        &mut self,
+        capture_count: usize,
+        f: impl FnOnce(&mut Self) -> UiuaResult + Send + 'static,
+    ) -> UiuaResult<Value> {
+        if self.stack.len() < capture_count {
+            return Err(self.error(format!(
+                "Excepted at least {} value(s) on the stack, but there are {}",
+                capture_count,
+                self.stack.len()
+            )))?;
+        }
+        let stack = self
+            .stack
+            .drain(self.stack.len() - capture_count..)
+            .collect();
+        let env = self.thread_env(stack);
         self.backend
-            .spawn(env, Box::new(f))
+            .spawn(env, Box::new(|env| env.limit_memory(f)))
             .map(Value::from)
             .map_err(|e| self.error(e))
     }
@@ -860,6 +1211,67 @@ code:
         }
         Ok(())
     }
+    /// Check if a function can be called on some values in parallel
+    ///
+    /// `count` is the number of calls. Splitting the environment between threads is only
+    /// worth it if the calls do enough work, which is estimated from the number of elements
+    /// and the number of instructions in the function.
+    pub(crate) fn can_call_parallel(&self, f: &Value, args: &Value, count: usize) -> bool {
+        if !self.parallel
+            || self.debugger.is_some()
+            || count < 2
+            || rayon::current_num_threads() < 2
+        {
+            return false;
+        }
+        let cost = f.as_function().map_or(1, |f| f.instrs.len().max(1));
+        let work = count.max(args.flat_len()).saturating_mul(cost);
+        work >= PARALLEL_MIN_WORK && f.is_pure() && args.is_pure()
+    }
+    /// Call a monadic function on each of some values in parallel
+    ///
+    /// The values are split between one environment per thread.
+    /// The results are in the same order as the values, and if any calls fail,
+    /// the error is the one from the earliest value.
+    pub(crate) fn call_parallel(
+        &mut self,
+        f: &Value,
+        args: Vec<Value>,
+        result_name: &'static str,
+    ) -> UiuaResult<Vec<Value>> {
+        let chunk_size = args.len().div_ceil(rayon::current_num_threads());
+        let mut args = args.into_iter();
+        let mut chunks = Vec::new();
+        loop {
+            let chunk: Vec<Value> = args.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            chunks.push((chunk, f.clone(), self.thread_env(Vec::new())));
+        }
+        // All chunks count toward the same memory limit
+        let budget = memory::current();
+        let results: Vec<UiuaResult<Vec<Value>>> = chunks
+            .into_par_iter()
+            .map(|(chunk, f, mut env)| {
+                let _shared = memory::share(budget.clone());
+                env.limit_memory(|env| {
+                    let mut results = Vec::with_capacity(chunk.len());
+                    for arg in chunk {
+                        env.push(arg);
+                        env.call(f.clone())?;
+                        results.push(env.pop(result_name)?);
+                    }
+                    Ok(results)
+                })
+            })
+            .collect();
+        let mut values = Vec::new();
+        for res in results {
+            values.extend(res?);
+        }
+        Ok(values)
+    }
 }
 
 /// A trait for types that can be used as argument specifiers for [`Uiua::pop`] and [`Uiua::antipop`]
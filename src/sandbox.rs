@@ -0,0 +1,524 @@
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::atomic::{self, AtomicUsize},
+    time::Duration,
+};
+
+use image::DynamicImage;
+use parking_lot::Mutex;
+
+use crate::{
+    value::Value, AudioStreamFn, FfiDecl, Handle, HttpResponse, NativeSys, SysBackend, Uiua,
+    UiuaError, UiuaResult,
+};
+
+/// A [`SysBackend`] that restricts what another backend is allowed to do
+///
+/// This is intended for running untrusted code.
+///
+/// By default, a sandbox:
+/// - cannot read or write any files
+/// - cannot run commands, change the working directory, or call foreign functions
+/// - cannot open TCP or UDP sockets or make HTTPS requests
+/// - cannot read environment variables
+///
+/// Printing, reading from stdin, images, audio, sleeping, threads, and channels are passed
+/// through to the wrapped backend.
+///
+/// A backend cannot see array allocations, so a sandbox does not limit memory use.
+/// Combine it with [`Uiua::with_memory_limit`] to do that.
+pub struct SandboxSys {
+    inner: Box<dyn SysBackend>,
+    read_dirs: Vec<PathBuf>,
+    write_dir: Option<PathBuf>,
+    max_handles: Option<usize>,
+    read_quota: Option<usize>,
+    handles: Mutex<HashSet<Handle>>,
+    /// Channels stay usable after they are closed until they are empty,
+    /// so they are tracked separately
+    channels: Mutex<HashMap<Handle, SandboxChannel>>,
+    bytes_read: AtomicUsize,
+}
+
+/// What a sandbox knows about one of its channels
+#[derive(Default)]
+struct SandboxChannel {
+    /// The number of values that have been sent but not received
+    len: usize,
+    closed: bool,
+}
+
+impl Default for SandboxSys {
+    fn default() -> Self {
+        Self::new(NativeSys)
+    }
+}
+
+impl SandboxSys {
+    /// Create a new sandbox around a backend
+    pub fn new(inner: impl SysBackend) -> Self {
+        Self {
+            inner: Box::new(inner),
+            read_dirs: Vec::new(),
+            write_dir: None,
+            max_handles: None,
+            read_quota: None,
+            handles: Mutex::new(HashSet::new()),
+            channels: Mutex::new(HashMap::new()),
+            bytes_read: AtomicUsize::new(0),
+        }
+    }
+    /// Allow reading files in a directory or any of its subdirectories
+    pub fn allow_read(mut self, dir: impl AsRef<Path>) -> Self {
+        self.read_dirs.push(canonical_dir(dir.as_ref()));
+        self
+    }
+    /// Allow creating and writing files in a scratch directory or any of its subdirectories
+    ///
+    /// Files in the scratch directory can also be read.
+    pub fn allow_write(mut self, dir: impl AsRef<Path>) -> Self {
+        self.write_dir = Some(canonical_dir(dir.as_ref()));
+        self
+    }
+    /// Limit the number of files, threads, and channels that can be open at once
+    pub fn with_max_handles(mut self, max_handles: usize) -> Self {
+        self.max_handles = Some(max_handles);
+        self
+    }
+    /// Limit the total number of bytes that can be read from files
+    ///
+    /// This does not limit memory use. Use [`Uiua::with_memory_limit`] for that.
+    pub fn with_read_quota(mut self, bytes: usize) -> Self {
+        self.read_quota = Some(bytes);
+        self
+    }
+    fn can_read(&self, path: &Path) -> bool {
+        (self.read_dirs.iter())
+            .chain(&self.write_dir)
+            .any(|dir| path.starts_with(dir))
+    }
+    /// Check that a path can be read, and get the resolved path to pass to the inner backend
+    ///
+    /// The resolved path is used so that a symlink swapped in after the check is not followed.
+    fn check_read(&self, path: &str) -> Result<String, String> {
+        match resolve(path) {
+            Some(resolved) if self.can_read(&resolved) => resolved_str(resolved, path),
+            _ => Err(format!("Reading {path} is not allowed in the sandbox")),
+        }
+    }
+    /// Check that a path can be written, and get the resolved path to pass to the inner backend
+    fn check_write(&self, path: &str) -> Result<String, String> {
+        match (resolve(path), &self.write_dir) {
+            (Some(resolved), Some(dir)) if resolved.starts_with(dir) => {
+                resolved_str(resolved, path)
+            }
+            _ => Err(format!("Writing {path} is not allowed in the sandbox")),
+        }
+    }
+    fn check_handle(&self, handle: Handle) -> Result<(), String> {
+        if handle < Handle::FIRST_UNRESERVED || self.handles.lock().contains(&handle) {
+            Ok(())
+        } else {
+            Err("Invalid file handle".into())
+        }
+    }
+    fn check_channel(&self, handle: Handle) -> Result<(), String> {
+        if self.channels.lock().contains_key(&handle) {
+            Ok(())
+        } else {
+            Err("Invalid channel handle".into())
+        }
+    }
+    /// Record that a value was received from a channel, or that receiving failed
+    ///
+    /// Channels that are closed and empty are forgotten.
+    fn received(&self, handle: Handle, received: bool) {
+        let mut channels = self.channels.lock();
+        if let Some(channel) = channels.get_mut(&handle) {
+            if received {
+                channel.len = channel.len.saturating_sub(1);
+            }
+            if channel.closed && (channel.len == 0 || !received) {
+                channels.remove(&handle);
+            }
+        }
+    }
+    fn check_handle_quota(&self) -> Result<(), String> {
+        match self.max_handles {
+            Some(max) if self.handles.lock().len() >= max => Err(format!(
+                "The sandbox allows at most {max} open handle{}",
+                if max == 1 { "" } else { "s" }
+            )),
+            _ => Ok(()),
+        }
+    }
+    fn add_handle(&self, handle: Result<Handle, String>) -> Result<Handle, String> {
+        let handle = handle?;
+        self.handles.lock().insert(handle);
+        Ok(handle)
+    }
+    fn check_read_quota(&self, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+        let total = self
+            .bytes_read
+            .fetch_add(bytes.len(), atomic::Ordering::Relaxed)
+            + bytes.len();
+        match self.read_quota {
+            Some(quota) if total > quota => Err(format!(
+                "The sandbox allows at most {quota} bytes to be read from files"
+            )),
+            _ => Ok(bytes),
+        }
+    }
+}
+
+fn resolved_str(resolved: PathBuf, path: &str) -> Result<String, String> {
+    (resolved.into_os_string().into_string())
+        .map_err(|_| format!("{path} is not a valid UTF-8 path"))
+}
+
+fn canonical_dir(dir: &Path) -> PathBuf {
+    dir.canonicalize().unwrap_or_else(|_| dir.into())
+}
+
+/// Resolve a path to an absolute one with no symlinks
+///
+/// Paths that do not exist yet are resolved relative to their parent
+fn resolve(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let name = path.file_name()?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(name))
+}
+
+impl SysBackend for SandboxSys {
+    fn any(&self) -> &dyn Any {
+        self
+    }
+    fn save_error_color(&self, error: &UiuaError) {
+        self.inner.save_error_color(error)
+    }
+    fn print_str_stdout(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stdout(s)
+    }
+    fn print_str_stderr(&self, s: &str) -> Result<(), String> {
+        self.inner.print_str_stderr(s)
+    }
+    fn print_str_trace(&self, s: &str) {
+        self.inner.print_str_trace(s)
+    }
+    fn scan_line_stdin(&self) -> Result<Option<String>, String> {
+        self.inner.scan_line_stdin()
+    }
+    fn var(&self, _name: &str) -> Option<String> {
+        None
+    }
+    fn term_size(&self) -> Result<(usize, usize), String> {
+        self.inner.term_size()
+    }
+    fn file_exists(&self, path: &str) -> bool {
+        match self.check_read(path) {
+            Ok(path) => self.inner.file_exists(&path),
+            Err(_) => false,
+        }
+    }
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, String> {
+        let resolved = self.check_read(path)?;
+        let entries = self.inner.list_dir(&resolved)?;
+        // Show entries relative to the path that was given
+        Ok((entries.into_iter())
+            .map(|entry| match Path::new(&entry).strip_prefix(&resolved) {
+                Ok(name) => Path::new(path).join(name).to_string_lossy().into(),
+                Err(_) => entry,
+            })
+            .collect())
+    }
+    fn is_file(&self, path: &str) -> Result<bool, String> {
+        let path = self.check_read(path)?;
+        self.inner.is_file(&path)
+    }
+    fn read(&self, handle: Handle, count: usize) -> Result<Vec<u8>, String> {
+        self.check_handle(handle)?;
+        let bytes = self.inner.read(handle, count)?;
+        if handle < Handle::FIRST_UNRESERVED {
+            Ok(bytes)
+        } else {
+            self.check_read_quota(bytes)
+        }
+    }
+    fn write(&self, handle: Handle, contents: &[u8]) -> Result<(), String> {
+        self.check_handle(handle)?;
+        self.inner.write(handle, contents)
+    }
+    fn create_file(&self, path: &str) -> Result<Handle, String> {
+        let path = self.check_write(path)?;
+        self.check_handle_quota()?;
+        self.add_handle(self.inner.create_file(&path))
+    }
+    fn open_file(&self, path: &str) -> Result<Handle, String> {
+        let path = self.check_read(path)?;
+        self.check_handle_quota()?;
+        self.add_handle(self.inner.open_file(&path))
+    }
+    fn file_read_all(&self, path: &str) -> Result<Vec<u8>, String> {
+        let path = self.check_read(path)?;
+        self.check_read_quota(self.inner.file_read_all(&path)?)
+    }
+    fn file_write_all(&self, path: &str, contents: &[u8]) -> Result<(), String> {
+        let path = self.check_write(path)?;
+        self.inner.file_write_all(&path, contents)
+    }
+    fn sleep(&self, seconds: f64) -> Result<(), String> {
+        self.inner.sleep(seconds)
+    }
+    fn show_image(&self, image: DynamicImage) -> Result<(), String> {
+        self.inner.show_image(image)
+    }
+    fn show_gif(&self, gif_bytes: Vec<u8>) -> Result<(), String> {
+        self.inner.show_gif(gif_bytes)
+    }
+    fn play_audio(&self, wave_bytes: Vec<u8>) -> Result<(), String> {
+        self.inner.play_audio(wave_bytes)
+    }
+    fn audio_sample_rate(&self) -> u32 {
+        self.inner.audio_sample_rate()
+    }
+    fn stream_audio(&self, f: AudioStreamFn) -> Result<(), String> {
+        self.inner.stream_audio(f)
+    }
+    fn tcp_listen(&self, _addr: &str) -> Result<Handle, String> {
+        Err("TCP listeners are not allowed in the sandbox".into())
+    }
+    fn tcp_accept(&self, _handle: Handle) -> Result<Handle, String> {
+        Err("TCP listeners are not allowed in the sandbox".into())
+    }
+    fn tcp_connect(&self, _addr: &str) -> Result<Handle, String> {
+        Err("TCP sockets are not allowed in the sandbox".into())
+    }
+    fn tcp_addr(&self, _handle: Handle) -> Result<String, String> {
+        Err("TCP sockets are not allowed in the sandbox".into())
+    }
+    fn tcp_set_non_blocking(&self, _handle: Handle, _non_blocking: bool) -> Result<(), String> {
+        Err("TCP sockets are not allowed in the sandbox".into())
+    }
+    fn tcp_set_read_timeout(
+        &self,
+        _handle: Handle,
+        _timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        Err("TCP sockets are not allowed in the sandbox".into())
+    }
+    fn tcp_set_write_timeout(
+        &self,
+        _handle: Handle,
+        _timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        Err("TCP sockets are not allowed in the sandbox".into())
+    }
+    fn udp_bind(&self, _addr: &str) -> Result<Handle, String> {
+        Err("UDP sockets are not allowed in the sandbox".into())
+    }
+    fn udp_send_to(&self, _handle: Handle, _addr: &str, _message: &[u8]) -> Result<(), String> {
+        Err("UDP sockets are not allowed in the sandbox".into())
+    }
+    fn udp_recv_from(&self, _handle: Handle) -> Result<(Vec<u8>, String), String> {
+        Err("UDP sockets are not allowed in the sandbox".into())
+    }
+    fn udp_set_read_timeout(
+        &self,
+        _handle: Handle,
+        _timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        Err("UDP sockets are not allowed in the sandbox".into())
+    }
+    fn udp_addr(&self, _handle: Handle) -> Result<String, String> {
+        Err("UDP sockets are not allowed in the sandbox".into())
+    }
+    fn ffi(&self, _file: &str, _decl: &FfiDecl, _args: &[Value]) -> Result<Value, String> {
+        Err("FFI is not allowed in the sandbox".into())
+    }
+    fn close(&self, handle: Handle) -> Result<(), String> {
+        self.check_handle(handle)?;
+        self.inner.close(handle)?;
+        self.handles.lock().remove(&handle);
+        let mut channels = self.channels.lock();
+        if let Some(channel) = channels.get_mut(&handle) {
+            if channel.len == 0 {
+                channels.remove(&handle);
+            } else {
+                channel.closed = true;
+            }
+        }
+        Ok(())
+    }
+    fn spawn(
+        &self,
+        env: Uiua,
+        f: Box<dyn FnOnce(&mut Uiua) -> UiuaResult + Send>,
+    ) -> Result<Handle, String> {
+        self.check_handle_quota()?;
+        self.add_handle(self.inner.spawn(env, f))
+    }
+    fn wait(&self, handle: Handle) -> Result<Vec<Value>, Result<UiuaError, String>> {
+        self.check_handle(handle).map_err(Err)?;
+        let res = self.inner.wait(handle);
+        self.handles.lock().remove(&handle);
+        res
+    }
+    fn channel(&self) -> Result<Handle, String> {
+        self.check_handle_quota()?;
+        let handle = self.add_handle(self.inner.channel())?;
+        (self.channels.lock()).insert(handle, SandboxChannel::default());
+        Ok(handle)
+    }
+    fn send(&self, handle: Handle, value: Value) -> Result<(), String> {
+        // The value is counted before it is sent so that a receiver never sees it uncounted
+        (self.channels.lock().get_mut(&handle))
+            .ok_or("Invalid channel handle")?
+            .len += 1;
+        let res = self.inner.send(handle, value);
+        if res.is_err() {
+            if let Some(channel) = self.channels.lock().get_mut(&handle) {
+                channel.len = channel.len.saturating_sub(1);
+            }
+        }
+        res
+    }
+    fn recv(&self, handle: Handle) -> Result<Value, String> {
+        self.check_channel(handle)?;
+        let res = self.inner.recv(handle);
+        self.received(handle, res.is_ok());
+        res
+    }
+    fn try_recv(&self, handle: Handle) -> Result<Option<Value>, String> {
+        self.check_channel(handle)?;
+        let res = self.inner.try_recv(handle);
+        match &res {
+            Ok(Some(_)) => self.received(handle, true),
+            Ok(None) => {}
+            Err(_) => self.received(handle, false),
+        }
+        res
+    }
+    fn run_command_inherit(&self, _command: &str, _args: &[&str]) -> Result<(), String> {
+        Err("Running commands is not allowed in the sandbox".into())
+    }
+    fn run_command_capture(
+        &self,
+        _command: &str,
+        _args: &[&str],
+    ) -> Result<(String, String), String> {
+        Err("Running commands is not allowed in the sandbox".into())
+    }
+    fn change_directory(&self, _path: &str) -> Result<(), String> {
+        Err("Changing directories is not allowed in the sandbox".into())
+    }
+    fn https_get(&self, _request: &str, _handle: Handle) -> Result<String, String> {
+        Err("Making HTTPS requests is not allowed in the sandbox".into())
+    }
+    fn http_request(
+        &self,
+        _host: &str,
+        _port: u16,
+        _tls: bool,
+        _request: &[u8],
+    ) -> Result<HttpResponse, String> {
+        Err("Making HTTP requests is not allowed in the sandbox".into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sandbox() {
+        let scratch = std::env::temp_dir().join("uiua_sandbox_test");
+        std::fs::create_dir_all(&scratch).unwrap();
+        let scratch_str = scratch.to_string_lossy().to_string();
+        let run = |code: &str| {
+            let backend = SandboxSys::default()
+                .allow_write(&scratch)
+                .with_max_handles(1)
+                .with_read_quota(10);
+            let mut env = Uiua::with_backend(backend);
+            env.load_str(code).map_err(|e| e.to_string())
+        };
+        let file = scratch
+            .join("a.txt")
+            .to_string_lossy()
+            .replace('\\', "\\\\");
+        assert!(run(&format!("&fwa \"{file}\" \"hello\"")).is_ok());
+        assert!(run(&format!("&fras \"{file}\"")).is_ok());
+        let err = run("&runc {\"ls\"}").unwrap_err();
+        assert!(err.contains("not allowed in the sandbox"), "{err}");
+        let err = run("&cd \".\"").unwrap_err();
+        assert!(err.contains("not allowed in the sandbox"), "{err}");
+        let err = run("&tcpc \"localhost:8080\"").unwrap_err();
+        assert!(err.contains("not allowed in the sandbox"), "{err}");
+        let err = run("&udpb \"127.0.0.1:0\"").unwrap_err();
+        assert!(err.contains("not allowed in the sandbox"), "{err}");
+        let err = run("&http \"http://localhost:8080\" \"GET\" [] \"\"").unwrap_err();
+        assert!(err.contains("not allowed in the sandbox"), "{err}");
+        let err = run("&fras \"Cargo.toml\"").unwrap_err();
+        assert!(err.contains("Reading Cargo.toml is not allowed"), "{err}");
+        let err = run(&format!("&fwa \"{scratch_str}/../b.txt\" \"hi\"")).unwrap_err();
+        assert!(err.contains("is not allowed in the sandbox"), "{err}");
+        let err = run(&format!("&fo \"{file}\" &fo \"{file}\"")).unwrap_err();
+        assert!(err.contains("at most 1 open handle"), "{err}");
+        let err = run(&format!("&fras \"{file}\" &fwa \"{file}\" \"hello world\"")).unwrap_err();
+        assert!(err.contains("at most 10 bytes"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sandbox_resolved_paths() {
+        use std::sync::Arc;
+
+        struct PathRecorder(Arc<Mutex<Vec<String>>>);
+        impl SysBackend for PathRecorder {
+            fn any(&self) -> &dyn Any {
+                self
+            }
+            fn file_read_all(&self, path: &str) -> Result<Vec<u8>, String> {
+                self.0.lock().push(path.into());
+                Ok(Vec::new())
+            }
+        }
+
+        let dir = std::env::temp_dir().join("uiua_sandbox_paths_test");
+        let real = dir.join("real");
+        let link = dir.join("link");
+        std::fs::create_dir_all(&real).unwrap();
+        std::fs::write(real.join("a.txt"), "a").unwrap();
+        _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let sandbox = SandboxSys::new(PathRecorder(paths.clone())).allow_read(&dir);
+        let through_link = link.join("a.txt").to_string_lossy().to_string();
+        sandbox.file_read_all(&through_link).unwrap();
+        let expected = real.canonicalize().unwrap().join("a.txt");
+        assert_eq!(*paths.lock(), [expected.to_string_lossy()]);
+    }
+
+    #[test]
+    fn sandbox_channels() {
+        let sandbox = SandboxSys::default();
+        let empty = sandbox.channel().unwrap();
+        sandbox.close(empty).unwrap();
+        assert!(sandbox.channels.lock().is_empty());
+        let full = sandbox.channel().unwrap();
+        sandbox.send(full, 1.0.into()).unwrap();
+        sandbox.close(full).unwrap();
+        assert_eq!(sandbox.recv(full), Ok(1.0.into()));
+        assert!(sandbox.channels.lock().is_empty());
+        assert!(sandbox.recv(full).is_err());
+    }
+}
@@ -3,7 +3,8 @@ use std::{
     collections::{HashMap, HashSet},
     env,
     fs::{self, File},
-    io::{stderr, stdin, stdout, BufRead, Cursor, Read, Write},
+    io::{self, stderr, stdin, stdout, BufRead, BufReader, Cursor, ErrorKind, Read, Write},
+    mem::take,
     net::*,
     process::Command,
     sync::{
@@ -15,6 +16,7 @@ use std::{
 };
 
 use bufreaderwriter::seq::BufReaderWriterSeq;
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use dashmap::DashMap;
 use enum_iterator::Sequence;
 use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
@@ -24,8 +26,8 @@ use parking_lot::Mutex;
 use tinyvec::tiny_vec;
 
 use crate::{
-    array::Array, cowslice::CowSlice, function::Function, grid_fmt::GridFmt, primitive::PrimDoc,
-    value::Value, Uiua, UiuaError, UiuaResult,
+    array::Array, cowslice::CowSlice, ffi::FfiDecl, function::Function, grid_fmt::GridFmt,
+    primitive::PrimDoc, value::Value, Uiua, UiuaError, UiuaResult,
 };
 
 pub fn example_ua<T>(f: impl FnOnce(&mut String) -> T) -> T {
@@ -163,7 +165,7 @@ sys_op! {
     (1, Import, "&i", "import"),
     /// Close a stream by its handle
     ///
-    /// This will close files, tcp listeners, and tcp sockets.
+    /// This will close files, tcp listeners, tcp sockets, udp sockets, and channels.
     (1(0), Close, "&cl", "close handle"),
     /// Open a file and return a handle to it
     ///
@@ -299,6 +301,159 @@ sys_op! {
     /// Expects a function that takes a list of sample times and returns a list of samples.
     /// The function will be called repeatedly to generate the audio.
     (1(0), AudioStream, "&ast", "audio - stream"),
+    /// Encode a value as JSON
+    ///
+    /// Numbers become JSON numbers. JSON has no `NaN` or infinity, so `NaN`, `∞`, and `¯∞` become `null`.
+    /// Character arrays become strings.
+    /// Rank `2` arrays of boxes with `2` columns where every key in the first column is a string become objects.
+    /// Other arrays become lists of their rows.
+    ///
+    /// ex: &jsone [1 2 3]
+    /// ex: &jsone {1 "two" [3 4]}
+    /// ex: &jsone [{"name" "Uiua"} {"age" 0}]
+    ///
+    /// See also: [&jsond]
+    (1, JsonEncode, "&jsone", "json - encode"),
+    /// Decode a value from JSON
+    ///
+    /// Expects a string or a byte array.
+    ///
+    /// Numbers become numbers, `true` and `false` become `1` and `0`, and `null` becomes `NaN`.
+    /// Strings become character arrays.
+    /// Lists whose items are all numbers, or all numeric arrays of the same shape, become numeric arrays.
+    /// Other lists become lists of boxes.
+    /// Objects become rank `2` arrays of boxes where each row is a key and a value.
+    ///
+    /// ex: &jsond "[1, 2, 3]"
+    /// ex: &jsond "[[1, 2], [3, 4]]"
+    /// ex: &jsond "[1, \"two\", [3, 4]]"
+    /// ex: &jsond "{\"name\": \"Uiua\", \"age\": 0}"
+    ///
+    /// See also: [&jsone]
+    (1, JsonDecode, "&jsond", "json - decode"),
+    /// Encode a value in a compact binary format
+    ///
+    /// The result is a list of bytes that keeps the value's shape and type.
+    /// Boxes are encoded, but functions are not.
+    /// This is useful for writing arrays to files with [&fwa] and reading them back with [&frab] and [&deser].
+    ///
+    /// ex: &ser [1_2 3_4]
+    /// ex: &ser "hi"
+    ///
+    /// See also: [&deser]
+    (1, Serialize, "&ser", "serialize"),
+    /// Decode a value encoded with [&ser]
+    ///
+    /// ex: &deser &ser [1_2 3_4]
+    /// ex: &deser &ser {"a" [1 2] 3}
+    ///
+    /// See also: [&ser]
+    (1, Deserialize, "&deser", "deserialize"),
+    /// Decode a CSV table
+    ///
+    /// The first argument is the delimiter, which must be a single character.
+    /// The second argument is the CSV text.
+    ///
+    /// Fields may be quoted with `"`. Quotes in quoted fields are escaped by doubling them.
+    ///
+    /// If every column is numeric, the result is a rank `2` numeric array.
+    /// Otherwise, the result is a rank `2` array of boxes.
+    /// ex: &csvd "," "1,2,3\n4,5,6"
+    /// ex: &csvd "," "Alice,30\n\"Smith, Bob\",45"
+    /// ex: &csvd @\t "a\tb\nc\td"
+    ///
+    /// See also: [&csvdh] [&csve]
+    (2, CsvDecode, "&csvd", "csv - decode"),
+    /// Decode a CSV table that has a header
+    ///
+    /// Takes the same arguments as [&csvd].
+    ///
+    /// The result is a rank `2` array of boxes with a row for each column.
+    /// Each row has the column's name and its values.
+    /// Numeric columns are numeric arrays, and other columns are lists of boxed strings.
+    /// ex: &csvdh "," "name,age\nAlice,30\nBob,45"
+    ///
+    /// See also: [&csvd] [&csve]
+    (2, CsvDecodeHeader, "&csvdh", "csv - decode with header"),
+    /// Encode a table as CSV
+    ///
+    /// The first argument is the delimiter, which must be a single character.
+    /// The second argument is the table.
+    ///
+    /// The table can be a rank `2` array of numbers, or a rank `2` array of boxed numbers and strings.
+    /// Fields that contain the delimiter, quotes, or newlines are quoted.
+    /// `NaN` becomes an empty field, which [&csvd] decodes as `NaN`.
+    /// ex: &csve "," [1_2_3 4_5_6]
+    /// ex: &csve "," [{"Alice" 30} {"Smith, Bob" 45}]
+    /// Tables with a header, like those returned by [&csvdh], are also supported.
+    /// ex: &csve "," &csvdh "," "name,age\nAlice,30\nBob,45"
+    ///
+    /// See also: [&csvw] [&csvd]
+    (2, CsvEncode, "&csve", "csv - encode"),
+    /// Encode a table as CSV and write it to a file
+    ///
+    /// The first argument is the path, and the second is the delimiter.
+    /// The third argument is the table, in any format supported by [&csve].
+    ///
+    /// See also: [&csve]
+    (3(0), CsvWrite, "&csvw", "csv - write"),
+    /// Create a channel for sending values between threads
+    ///
+    /// The channel's handle can be passed to [&send], [&recv], and [&tryrecv].
+    /// Closing the channel with [&cl] lets receivers know that no more values will be sent.
+    /// ex: Ch ← &chan
+    ///   : &send Ch 5
+    ///   : &recv Ch
+    ///
+    /// Threads can use a channel to pass values to each other while they are running.
+    /// ex: Ch ← &chan
+    ///   : ↲↰(&send Ch ×2) 5
+    ///   : &recv Ch
+    (0, Channel, "&chan", "channel - new"),
+    /// Send a value through a channel
+    ///
+    /// The first argument is the channel's handle, and the second is the value.
+    /// Sending does not wait for the value to be received.
+    ///
+    /// See also: [&chan] [&recv]
+    (2(0), Send, "&send", "channel - send"),
+    /// Receive a value from a channel
+    ///
+    /// Waits until a value has been sent if the channel is empty.
+    /// It is an error to receive from a channel that has been closed with [&cl] and is empty.
+    ///
+    /// In the web editor, threads do not run at the same time, so receiving from an empty channel is an error.
+    ///
+    /// See also: [&chan] [&tryrecv]
+    (1, Receive, "&recv", "channel - receive"),
+    /// Receive a value from a channel if one is available
+    ///
+    /// Unlike [&recv], this does not wait. It is an error if the channel is empty.
+    /// Use [try] to handle the error.
+    /// ex: Ch ← &chan
+    ///   : ⍣(&tryrecv Ch)(0;)
+    ///
+    /// See also: [&chan] [&recv]
+    (1, TryReceive, "&tryrecv", "channel - try receive"),
+    /// Call a function in a C shared library
+    ///
+    /// The first argument is the path to the library, and the second is a C declaration of the function.
+    /// The third argument is a list of the function's arguments.
+    ///
+    /// Supported types are `int`, `long`, `double`, and pointers to `double` or `unsigned char` buffers.
+    /// Buffers are passed as numeric arrays. The function may also return `void`.
+    /// Pointers to data that is not `const` are out-parameters. If there are any, the result is
+    /// a list of the boxed return value followed by the boxed out-parameters.
+    /// For example, `void scale(double* xs, int n, double k)` called with `{[1 2 3] 3 10}` returns `{[10 20 30]}`.
+    ///
+    /// `long` is a 64-bit integer, and `long` arguments must fit in 64 bits.
+    /// Calling a variadic function like `printf`, calling a function with the wrong declaration,
+    /// or passing buffers that are too short is undefined behavior.
+    ///
+    /// This is only available if the interpreter was built with the `ffi` feature.
+    ///
+    /// ex: &ffi "libm.so.6" "double cos(double x)" {0}
+    (3, Ffi, "&ffi", "foreign function interface"),
     /// Create a TCP listener and bind it to an address
     (1, TcpListen, "&tcpl", "tcp - listen"),
     /// Accept a connection with a TCP listener
@@ -312,7 +467,68 @@ sys_op! {
     /// Set the write timeout of a TCP socket in seconds
     (2(0), TcpSetWriteTimeout, "&tcpswt", "tcp - set write timeout"),
     /// Get the connection address of a TCP socket
+    ///
+    /// For a TCP listener, this is the address it is listening on.
+    /// This is useful for finding the port chosen when listening on port `0`.
     (1, TcpAddr, "&tcpaddr", "tcp - address"),
+    /// Create a UDP socket and bind it to an address
+    ///
+    /// Binding to port `0` lets the operating system choose a port.
+    (1, UdpBind, "&udpb", "udp - bind"),
+    /// Send a message with a UDP socket
+    ///
+    /// The first argument is the message, which can be a string or a byte array.
+    /// The second argument is the address to send it to, and the third is the socket's handle.
+    (3(0), UdpSendTo, "&udps", "udp - send to"),
+    /// Receive a message with a UDP socket
+    ///
+    /// Waits until a message arrives.
+    /// The message is pushed as a byte array on top of the address that sent it.
+    /// Messages longer than 65507 bytes are truncated.
+    (1(2), UdpReceiveFrom, "&udpr", "udp - receive from"),
+    /// Set the read timeout of a UDP socket in seconds
+    ///
+    /// Receiving with [&udpr] is an error if no message arrives in time.
+    (2(0), UdpSetReadTimeout, "&udpsrt", "udp - set read timeout"),
+    /// Get the local address of a UDP socket
+    ///
+    /// This is useful for finding the port chosen when binding to port `0`.
+    (1, UdpAddr, "&udpaddr", "udp - address"),
+    /// Read an HTTP request from a TCP socket
+    ///
+    /// Waits until a full request has been received.
+    /// The result is a list of 4 boxes: the method, the path, the headers, and the body.
+    /// The headers are a table of boxed name and value strings, and the body is a byte array.
+    ///
+    /// Chunked request bodies are not supported.
+    (1, HttpReadRequest, "&httpr", "http - read request"),
+    /// Write an HTTP response to a TCP socket
+    ///
+    /// The first argument is the status, which can be a code like `404` or a string like `"404 Not Found"`.
+    /// The second argument is a table of boxed header names and values, or an empty list.
+    /// The third argument is the body, which can be a string or a byte array.
+    /// The fourth argument is the socket's handle.
+    ///
+    /// A `Content-Length` header is added unless there is a `Content-Length` or `Transfer-Encoding` header already.
+    /// It is an error for a header name to not be a valid HTTP token, or for the status or a header value to contain a line break.
+    (4(0), HttpWriteResponse, "&httpw", "http - write response"),
+    /// Make an HTTP request to a URL
+    ///
+    /// The first argument is the URL, which must start with `http://` or `https://`.
+    /// The second argument is the method, like `"GET"` or `"POST"`.
+    /// The third argument is a table of boxed header names and values, or an empty list.
+    /// The fourth argument is the body, which can be a string or a byte array.
+    ///
+    /// The response's status code is pushed on top of a table of its headers, which is on top of its body as a byte array.
+    /// `Host`, `Content-Length`, and `Connection` headers are added if they are not given.
+    /// Responses with a `Content-Length`, with chunked transfer encoding, or that end when the connection closes are all supported.
+    ///
+    /// Spaces and other characters that cannot appear in a request are percent-encoded in the URL's path.
+    /// It is an error for a header name to not be a valid HTTP token, or for a header value to contain a line break.
+    /// Connecting times out after 30 seconds, and waiting for more of the response times out after 60 seconds.
+    ///
+    /// `https://` URLs require the interpreter to be built with the `https` feature.
+    (4(3), HttpRequest, "&http", "http - request"),
     /// Make an HTTP request
     ///
     /// Takes in an 1.x HTTP request and returns an HTTP response.
@@ -363,7 +579,7 @@ impl From<Handle> for Value {
     }
 }
 
-type AudioStreamFn = Box<dyn FnMut(Vec<f64>) -> UiuaResult<Vec<[f64; 2]>> + Send>;
+pub type AudioStreamFn = Box<dyn FnMut(Vec<f64>) -> UiuaResult<Vec<[f64; 2]>> + Send>;
 
 #[allow(unused_variables)]
 pub trait SysBackend: Any + Send + Sync + 'static {
@@ -486,9 +702,33 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     ) -> Result<(), String> {
         Err("TCP sockets are not supported in this environment".into())
     }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    fn udp_send_to(&self, handle: Handle, addr: &str, message: &[u8]) -> Result<(), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    /// Receive a message and the address it came from
+    fn udp_recv_from(&self, handle: Handle) -> Result<(Vec<u8>, String), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    fn udp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        Err("UDP sockets are not supported in this environment".into())
+    }
     fn close(&self, handle: Handle) -> Result<(), String> {
         Ok(())
     }
+    /// Call a function in a C shared library
+    fn ffi(&self, file: &str, decl: &FfiDecl, args: &[Value]) -> Result<Value, String> {
+        Err("FFI is not supported in this environment".into())
+    }
     fn spawn(
         &self,
         env: Uiua,
@@ -501,6 +741,27 @@ pub trait SysBackend: Any + Send + Sync + 'static {
             "Joining threads is not supported in this environment".into()
         ))
     }
+    /// Create a channel
+    ///
+    /// Closing the channel's handle should stop values from being sent to it.
+    fn channel(&self) -> Result<Handle, String> {
+        Err("Channels are not supported in this environment".into())
+    }
+    fn send(&self, handle: Handle, value: Value) -> Result<(), String> {
+        Err("Channels are not supported in this environment".into())
+    }
+    /// Receive a value from a channel, waiting for one if it is empty
+    ///
+    /// Should return an error if the channel is closed and empty.
+    fn recv(&self, handle: Handle) -> Result<Value, String> {
+        Err("Channels are not supported in this environment".into())
+    }
+    /// Receive a value from a channel without waiting
+    ///
+    /// Should return `Ok(None)` if the channel is empty but not closed.
+    fn try_recv(&self, handle: Handle) -> Result<Option<Value>, String> {
+        Err("Channels are not supported in this environment".into())
+    }
     fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<(), String> {
         Err("Running commands is not supported in this environment".into())
     }
@@ -517,11 +778,41 @@ pub trait SysBackend: Any + Send + Sync + 'static {
     fn https_get(&self, request: &str, handle: Handle) -> Result<String, String> {
         Err("Making HTTPS requests is not supported in this environment".into())
     }
+    /// Send an HTTP request to a host and read its response
+    ///
+    /// The request is sent over TLS if `tls` is true.
+    fn http_request(
+        &self,
+        host: &str,
+        port: u16,
+        tls: bool,
+        request: &[u8],
+    ) -> Result<HttpResponse, String> {
+        Err("Making HTTP requests is not supported in this environment".into())
+    }
 }
 
 #[derive(Default)]
 pub struct NativeSys;
 
+// https://github.com/rustls/rustls/blob/c9cfe3499681361372351a57a00ccd793837ae9c/examples/src/bin/simpleclient.rs
+#[cfg(feature = "https")]
+static TLS_CLIENT_CONFIG: Lazy<Arc<rustls::ClientConfig>> = Lazy::new(|| {
+    let mut store = rustls::RootCertStore::empty();
+    store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(store)
+        .with_no_client_auth()
+        .into()
+});
+
 type Buffered<T> = BufReaderWriterSeq<T>;
 
 struct GlobalNativeSys {
@@ -529,8 +820,12 @@ struct GlobalNativeSys {
     files: DashMap<Handle, Buffered<File>>,
     tcp_listeners: DashMap<Handle, TcpListener>,
     tcp_sockets: DashMap<Handle, Buffered<TcpStream>>,
+    udp_sockets: DashMap<Handle, UdpSocket>,
     hostnames: DashMap<Handle, String>,
     threads: DashMap<Handle, JoinHandle<UiuaResult<Vec<Value>>>>,
+    channels: DashMap<Handle, Channel>,
+    #[cfg(feature = "ffi")]
+    ffi_libs: DashMap<String, Arc<libloading::Library>>,
     #[cfg(feature = "audio")]
     audio_stream_time: Mutex<Option<f64>>,
     #[cfg(feature = "audio")]
@@ -538,6 +833,21 @@ struct GlobalNativeSys {
     colored_errors: DashMap<String, String>,
 }
 
+/// A channel between threads
+///
+/// The sender is dropped when the channel is closed.
+struct Channel {
+    send: Option<Sender<Value>>,
+    recv: Receiver<Value>,
+}
+
+/// Remove a channel if it is closed and empty, since it can no longer be used
+fn remove_drained_channel(handle: Handle) {
+    NATIVE_SYS.channels.remove_if(&handle, |_, channel| {
+        channel.send.is_none() && channel.recv.is_empty()
+    });
+}
+
 enum SysStream<'a> {
     File(dashmap::mapref::one::RefMut<'a, Handle, Buffered<File>>),
     TcpListener(dashmap::mapref::one::RefMut<'a, Handle, TcpListener>),
@@ -551,8 +861,12 @@ impl Default for GlobalNativeSys {
             files: DashMap::new(),
             tcp_listeners: DashMap::new(),
             tcp_sockets: DashMap::new(),
+            udp_sockets: DashMap::new(),
             hostnames: DashMap::new(),
             threads: DashMap::new(),
+            channels: DashMap::new(),
+            #[cfg(feature = "ffi")]
+            ffi_libs: DashMap::new(),
             #[cfg(feature = "audio")]
             audio_stream_time: Mutex::new(None),
             #[cfg(feature = "audio")]
@@ -569,6 +883,9 @@ impl GlobalNativeSys {
             if !self.files.contains_key(&handle)
                 && !self.tcp_listeners.contains_key(&handle)
                 && !self.tcp_sockets.contains_key(&handle)
+                && !self.udp_sockets.contains_key(&handle)
+                && !self.threads.contains_key(&handle)
+                && !self.channels.contains_key(&handle)
             {
                 return handle;
             }
@@ -821,12 +1138,14 @@ impl SysBackend for NativeSys {
         Ok(handle)
     }
     fn tcp_accept(&self, handle: Handle) -> Result<Handle, String> {
+        // The listener is cloned so that the map is not locked while waiting
         let listener = NATIVE_SYS
             .tcp_listeners
-            .get_mut(&handle)
-            .ok_or_else(|| "Invalid tcp listener handle".to_string())?;
+            .get(&handle)
+            .ok_or_else(|| "Invalid tcp listener handle".to_string())?
+            .try_clone()
+            .map_err(|e| e.to_string())?;
         let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
-        drop(listener);
         let handle = NATIVE_SYS.new_handle();
         NATIVE_SYS
             .tcp_sockets
@@ -849,6 +1168,12 @@ impl SysBackend for NativeSys {
         Ok(handle)
     }
     fn tcp_addr(&self, handle: Handle) -> Result<String, String> {
+        if let Some(listener) = NATIVE_SYS.tcp_listeners.get(&handle) {
+            return Ok(listener
+                .local_addr()
+                .map_err(|e| e.to_string())?
+                .to_string());
+        }
         let socket = NATIVE_SYS
             .tcp_sockets
             .get(&handle)
@@ -900,17 +1225,88 @@ impl SysBackend for NativeSys {
             .map_err(|e| e.to_string())?;
         Ok(())
     }
+    fn udp_bind(&self, addr: &str) -> Result<Handle, String> {
+        let handle = NATIVE_SYS.new_handle();
+        let socket = UdpSocket::bind(addr).map_err(|e| e.to_string())?;
+        NATIVE_SYS.udp_sockets.insert(handle, socket);
+        Ok(handle)
+    }
+    fn udp_send_to(&self, handle: Handle, addr: &str, message: &[u8]) -> Result<(), String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        socket.send_to(message, addr).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+    fn udp_recv_from(&self, handle: Handle) -> Result<(Vec<u8>, String), String> {
+        // The socket is cloned so that the map is not locked while waiting
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?
+            .try_clone()
+            .map_err(|e| e.to_string())?;
+        let mut buffer = vec![0; 65507];
+        let (len, addr) = socket.recv_from(&mut buffer).map_err(|e| match e.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => "Timed out waiting for a message".into(),
+            _ => e.to_string(),
+        })?;
+        buffer.truncate(len);
+        Ok((buffer, addr.to_string()))
+    }
+    fn udp_set_read_timeout(
+        &self,
+        handle: Handle,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        socket
+            .set_read_timeout(timeout)
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+    fn udp_addr(&self, handle: Handle) -> Result<String, String> {
+        let socket = NATIVE_SYS
+            .udp_sockets
+            .get(&handle)
+            .ok_or_else(|| "Invalid udp socket handle".to_string())?;
+        Ok(socket.local_addr().map_err(|e| e.to_string())?.to_string())
+    }
     fn close(&self, handle: Handle) -> Result<(), String> {
         if NATIVE_SYS.files.remove(&handle).is_some()
             || NATIVE_SYS.tcp_listeners.remove(&handle).is_some()
-            || (NATIVE_SYS.tcp_sockets.remove(&handle).is_some()
-                && NATIVE_SYS.hostnames.remove(&handle).is_some())
+            || (NATIVE_SYS.tcp_sockets.remove(&handle))
+                .map(|_| NATIVE_SYS.hostnames.remove(&handle))
+                .is_some()
+            || NATIVE_SYS.udp_sockets.remove(&handle).is_some()
+            || (NATIVE_SYS.channels.get_mut(&handle))
+                .map(|mut channel| channel.send = None)
+                .is_some()
         {
+            remove_drained_channel(handle);
             Ok(())
         } else {
             Err("Invalid stream handle".to_string())
         }
     }
+    #[cfg(feature = "ffi")]
+    fn ffi(&self, file: &str, decl: &FfiDecl, args: &[Value]) -> Result<Value, String> {
+        let lib = NATIVE_SYS
+            .ffi_libs
+            .entry(file.into())
+            .or_try_insert_with(|| {
+                // Safety: loading a library runs its initialization code,
+                // which is trusted the same way as the functions it contains
+                let lib = unsafe { libloading::Library::new(file) };
+                lib.map(Arc::new).map_err(|e| e.to_string())
+            })?
+            .clone();
+        crate::ffi::ffi_call(&lib, decl, args)
+    }
     fn spawn(
         &self,
         mut env: Uiua,
@@ -935,6 +1331,54 @@ impl SysBackend for NativeSys {
             Err(e) => Err(Err(format!("Thread panicked: {:?}", e))),
         }
     }
+    fn channel(&self) -> Result<Handle, String> {
+        let (send, recv) = crossbeam_channel::unbounded();
+        let handle = NATIVE_SYS.new_handle();
+        let channel = Channel {
+            send: Some(send),
+            recv,
+        };
+        NATIVE_SYS.channels.insert(handle, channel);
+        Ok(handle)
+    }
+    fn send(&self, handle: Handle, value: Value) -> Result<(), String> {
+        // The map entry is not held while sending, so that other threads can use the channel
+        let send = (NATIVE_SYS.channels.get(&handle))
+            .ok_or("Invalid channel handle")?
+            .send
+            .clone()
+            .ok_or("The channel is closed")?;
+        send.send(value).map_err(|_| "The channel is closed".into())
+    }
+    fn recv(&self, handle: Handle) -> Result<Value, String> {
+        let recv = (NATIVE_SYS.channels.get(&handle))
+            .ok_or("Invalid channel handle")?
+            .recv
+            .clone();
+        let value = recv.recv().map_err(|_| {
+            NATIVE_SYS.channels.remove(&handle);
+            "The channel is closed".to_string()
+        })?;
+        remove_drained_channel(handle);
+        Ok(value)
+    }
+    fn try_recv(&self, handle: Handle) -> Result<Option<Value>, String> {
+        let recv = (NATIVE_SYS.channels.get(&handle))
+            .ok_or("Invalid channel handle")?
+            .recv
+            .clone();
+        match recv.try_recv() {
+            Ok(value) => {
+                remove_drained_channel(handle);
+                Ok(Some(value))
+            }
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => {
+                NATIVE_SYS.channels.remove(&handle);
+                Err("The channel is closed".into())
+            }
+        }
+    }
     fn run_command_inherit(&self, command: &str, args: &[&str]) -> Result<(), String> {
         Command::new(command)
             .args(args)
@@ -969,23 +1413,6 @@ impl SysBackend for NativeSys {
             .ok_or_else(|| "Invalid tcp socket handle".to_string())?;
         let request = check_http(request.to_string(), &host)?;
 
-        // https://github.com/rustls/rustls/blob/c9cfe3499681361372351a57a00ccd793837ae9c/examples/src/bin/simpleclient.rs
-        static CLIENT_CONFIG: Lazy<Arc<rustls::ClientConfig>> = Lazy::new(|| {
-            let mut store = rustls::RootCertStore::empty();
-            store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
-                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
-                    ta.subject,
-                    ta.spki,
-                    ta.name_constraints,
-                )
-            }));
-            rustls::ClientConfig::builder()
-                .with_safe_defaults()
-                .with_root_certificates(store)
-                .with_no_client_auth()
-                .into()
-        });
-
         let mut socket = NATIVE_SYS
             .tcp_sockets
             .get_mut(&handle)
@@ -994,7 +1421,7 @@ impl SysBackend for NativeSys {
         let server_name = rustls::ServerName::try_from(host.as_str()).map_err(|e| e.to_string())?;
         let tcp_stream = socket.get_mut();
 
-        let mut conn = rustls::ClientConnection::new(CLIENT_CONFIG.clone(), server_name)
+        let mut conn = rustls::ClientConnection::new(TLS_CLIENT_CONFIG.clone(), server_name)
             .map_err(|e| e.to_string())?;
         let mut tls = rustls::Stream::new(&mut conn, tcp_stream);
 
@@ -1008,6 +1435,53 @@ impl SysBackend for NativeSys {
 
         Ok(s)
     }
+    fn http_request(
+        &self,
+        host: &str,
+        port: u16,
+        tls: bool,
+        request: &[u8],
+    ) -> Result<HttpResponse, String> {
+        let mut stream = http_connect(host, port).map_err(|e| e.to_string())?;
+        // Responses to HEAD requests have no body
+        let has_body = !request.starts_with(b"HEAD ");
+        if !tls {
+            stream.write_all(request).map_err(|e| e.to_string())?;
+            return read_http_response(&mut BufReader::new(stream), has_body);
+        }
+        #[cfg(feature = "https")]
+        {
+            let server_name = rustls::ServerName::try_from(host).map_err(|e| e.to_string())?;
+            let conn = rustls::ClientConnection::new(TLS_CLIENT_CONFIG.clone(), server_name)
+                .map_err(|e| e.to_string())?;
+            let mut stream = rustls::StreamOwned::new(conn, stream);
+            stream.write_all(request).map_err(|e| e.to_string())?;
+            read_http_response(&mut BufReader::new(stream), has_body)
+        }
+        #[cfg(not(feature = "https"))]
+        Err("HTTPS requests require the https feature".into())
+    }
+}
+
+/// How long [`SysOp::HttpRequest`] waits to connect to a server
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long [`SysOp::HttpRequest`] waits for a server to send more of a response
+const HTTP_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Connect to an HTTP server, trying each of its addresses in turn
+fn http_connect(host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut error = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, HTTP_CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(HTTP_READ_TIMEOUT))?;
+                stream.set_write_timeout(Some(HTTP_READ_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(e) => error = Some(e),
+        }
+    }
+    Err(error.unwrap_or_else(|| io::Error::new(ErrorKind::NotFound, "Host has no addresses")))
 }
 
 /// Takes an HTTP request, validates it, and fixes it (if possible) by adding
@@ -1098,6 +1572,379 @@ fn check_http(mut request: String, hostname: &str) -> Result<String, String> {
     Ok(request)
 }
 
+/// The parsed head of an HTTP request
+struct HttpRequestHead {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    content_length: usize,
+}
+
+fn parse_http_request(head: &[u8]) -> Result<HttpRequestHead, String> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut req = httparse::Request::new(&mut headers);
+    match req.parse(head) {
+        Ok(httparse::Status::Complete(_)) => {}
+        Ok(httparse::Status::Partial) => return Err("Incomplete HTTP request".into()),
+        Err(e) => return Err(format!("Failed to parse HTTP request: {e}")),
+    }
+    let method = req.method.ok_or("No method in HTTP request")?.to_string();
+    let path = req.path.ok_or("No path in HTTP request")?.to_string();
+    let mut content_length = 0;
+    let mut headers = Vec::with_capacity(req.headers.len());
+    for header in req.headers.iter() {
+        let value = String::from_utf8_lossy(header.value).into_owned();
+        if header.name.eq_ignore_ascii_case("content-length") {
+            content_length = (value.trim().parse())
+                .map_err(|_| format!("Invalid Content-Length header {value:?}"))?;
+        } else if header.name.eq_ignore_ascii_case("transfer-encoding")
+            && value.to_ascii_lowercase().contains("chunked")
+        {
+            return Err("Chunked HTTP request bodies are not supported".into());
+        }
+        headers.push((header.name.to_string(), value));
+    }
+    Ok(HttpRequestHead {
+        method,
+        path,
+        headers,
+        content_length,
+    })
+}
+
+/// Get a string from a value that may be boxed
+fn http_string(value: &Value, requirement: &str) -> Result<String, String> {
+    match value {
+        Value::Char(arr) if arr.rank() <= 1 => Ok(arr.data.iter().collect()),
+        Value::Func(arr) if arr.rank() == 0 => http_cell(&arr.data[0], requirement),
+        value => Err(format!(
+            "{requirement}, but it is a {} array of shape {}",
+            value.type_name(),
+            value.format_shape()
+        )),
+    }
+}
+
+fn http_cell(f: &Function, requirement: &str) -> Result<String, String> {
+    match f.as_constant() {
+        Some(value) => http_string(value, requirement),
+        None => Err(format!("{requirement}, but it is a function")),
+    }
+}
+
+/// Convert a table of boxed names and values to HTTP headers
+fn value_to_headers(headers: Value) -> Result<Vec<(String, String)>, String> {
+    if headers.flat_len() == 0 {
+        return Ok(Vec::new());
+    }
+    let requirement = "Headers must be a table of names and values";
+    let Value::Func(headers) = &headers else {
+        return Err(format!(
+            "{requirement}, but they are {}",
+            headers.type_name()
+        ));
+    };
+    if headers.rank() != 2 || headers.shape[1] != 2 {
+        return Err(format!(
+            "{requirement}, but they have shape {}",
+            headers.format_shape()
+        ));
+    }
+    (headers.data.chunks_exact(2))
+        .map(|pair| {
+            let name = http_cell(&pair[0], "Header names must be strings")?;
+            let value = http_cell(&pair[1], "Header values must be strings")?;
+            if !is_http_token(&name) {
+                return Err(format!("{name:?} is not a valid header name"));
+            }
+            check_http_text(&value, "Header value")?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Check whether a string is a valid HTTP token, such as a header name or method
+fn is_http_token(s: &str) -> bool {
+    !s.is_empty()
+        && (s.bytes()).all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Make sure a string cannot end the line it is written on
+fn check_http_text(s: &str, what: &str) -> Result<(), String> {
+    if s.contains(['\r', '\n', '\0']) {
+        Err(format!(
+            "{what} {s:?} contains a line break or null character"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Convert HTTP headers to a table of boxed names and values
+fn headers_to_value(headers: Vec<(String, String)>) -> Value {
+    let boxed = |value: Value| Arc::new(Function::constant(value));
+    let len = headers.len();
+    let data: Vec<_> = (headers.into_iter())
+        .flat_map(|(name, value)| [boxed(name.into()), boxed(value.into())])
+        .collect();
+    Array::new(tiny_vec![len, 2], data).into()
+}
+
+fn has_header(headers: &[(String, String)], name: &str) -> bool {
+    headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+}
+
+/// Build an HTTP response from a status, a table of headers, and a body
+fn http_response(status: Value, headers: Value, body: Vec<u8>) -> Result<Vec<u8>, String> {
+    let status = match &status {
+        Value::Num(arr) if arr.rank() == 0 => http_status_line(arr.data[0])?,
+        Value::Byte(arr) if arr.rank() == 0 => http_status_line(arr.data[0] as f64)?,
+        status => http_string(status, "Status must be a number or a string")?,
+    };
+    check_http_text(&status, "Status")?;
+    let headers = value_to_headers(headers)?;
+    let mut response = format!("HTTP/1.1 {status}\r\n");
+    for (name, value) in &headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if !has_header(&headers, "content-length") && !has_header(&headers, "transfer-encoding") {
+        response.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    response.push_str("\r\n");
+    let mut response = response.into_bytes();
+    response.extend(body);
+    Ok(response)
+}
+
+/// The parts of an HTTP URL
+struct HttpUrl {
+    tls: bool,
+    /// The host and port as they appear in the URL
+    authority: String,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+fn parse_http_url(url: &str) -> Result<HttpUrl, String> {
+    let (tls, rest) = if let Some(rest) = url.strip_prefix("http://") {
+        (false, rest)
+    } else if let Some(rest) = url.strip_prefix("https://") {
+        (true, rest)
+    } else {
+        return Err(format!(
+            "URL must start with http:// or https://, but it is {url:?}"
+        ));
+    };
+    let (authority, path) = match rest.find(['/', '?', '#']) {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, ""),
+    };
+    // Fragments are not sent to the server
+    let raw_path = path.split('#').next().unwrap_or_default();
+    let mut path = if raw_path.starts_with('/') {
+        String::new()
+    } else {
+        "/".into()
+    };
+    // Spaces, control characters, and non-ASCII characters cannot appear in a request line
+    for b in raw_path.bytes() {
+        if b.is_ascii_graphic() {
+            path.push(b as char);
+        } else {
+            path.push_str(&format!("%{b:02X}"));
+        }
+    }
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => {
+            let port = (port.parse()).map_err(|_| format!("Invalid port {port:?} in URL"))?;
+            (host, port)
+        }
+        _ => (authority, if tls { 443 } else { 80 }),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(format!("URL {url:?} has no host"));
+    }
+    if !authority.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(format!("URL {url:?} has an invalid host"));
+    }
+    Ok(HttpUrl {
+        tls,
+        authority: authority.into(),
+        host: host.into(),
+        port,
+        path,
+    })
+}
+
+/// Build an HTTP request, adding any missing headers that are needed
+fn build_http_request(
+    method: &str,
+    url: &HttpUrl,
+    headers: &[(String, String)],
+    body: Vec<u8>,
+) -> Vec<u8> {
+    let mut request = format!("{method} {} HTTP/1.1\r\n", url.path);
+    if !has_header(headers, "host") {
+        request.push_str(&format!("Host: {}\r\n", url.authority));
+    }
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if !has_header(headers, "content-length")
+        && !has_header(headers, "transfer-encoding")
+        && (!body.is_empty() || matches!(method, "POST" | "PUT" | "PATCH"))
+    {
+        request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    if !has_header(headers, "connection") {
+        request.push_str("Connection: close\r\n");
+    }
+    request.push_str("\r\n");
+    let mut request = request.into_bytes();
+    request.extend(body);
+    request
+}
+
+/// A response to an HTTP request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Read an HTTP response
+///
+/// Responses to `HEAD` requests have no body, so `has_body` should be false for them.
+fn read_http_response(reader: &mut impl BufRead, has_body: bool) -> Result<HttpResponse, String> {
+    loop {
+        let mut head = Vec::new();
+        while !(head.ends_with(b"\r\n\r\n") || head.ends_with(b"\n\n")) {
+            let len = (reader.read_until(b'\n', &mut head)).map_err(|e| e.to_string())?;
+            if len == 0 {
+                return Err("The connection closed before an HTTP response was received".into());
+            }
+        }
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut res = httparse::Response::new(&mut headers);
+        match res.parse(&head) {
+            Ok(httparse::Status::Complete(_)) => {}
+            Ok(httparse::Status::Partial) => return Err("Incomplete HTTP response".into()),
+            Err(e) => return Err(format!("Failed to parse HTTP response: {e}")),
+        }
+        let status = res.code.ok_or("No status in HTTP response")?;
+        // Informational responses are followed by the real one
+        if (100..200).contains(&status) && status != 101 {
+            continue;
+        }
+        let headers: Vec<(String, String)> = (res.headers.iter())
+            .map(|h| (h.name.into(), String::from_utf8_lossy(h.value).into()))
+            .collect();
+        let header = |name: &str| {
+            (headers.iter())
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        };
+        let mut body = Vec::new();
+        if !has_body || status == 101 || status == 204 || status == 304 {
+        } else if header("transfer-encoding")
+            .is_some_and(|te| te.to_ascii_lowercase().contains("chunked"))
+        {
+            read_chunked_body(reader, &mut body)?;
+        } else if let Some(len) = header("content-length") {
+            let len: usize = (len.trim().parse())
+                .map_err(|_| format!("Invalid Content-Length header {len:?}"))?;
+            read_exactly(reader, len, &mut body)?;
+        } else {
+            match reader.read_to_end(&mut body) {
+                Ok(_) => {}
+                // TLS connections may end without a proper close
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {}
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        return Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        });
+    }
+}
+
+fn read_exactly(reader: &mut impl Read, len: usize, buf: &mut Vec<u8>) -> Result<(), String> {
+    let read = (Read::by_ref(reader).take(len as u64))
+        .read_to_end(buf)
+        .map_err(|e| e.to_string())?;
+    if read < len {
+        return Err("The connection closed before the HTTP response body was received".into());
+    }
+    Ok(())
+}
+
+fn read_chunked_body(reader: &mut impl BufRead, body: &mut Vec<u8>) -> Result<(), String> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| format!("Invalid chunk size {size:?} in HTTP response"))?;
+        if size == 0 {
+            break;
+        }
+        read_exactly(reader, size, body)?;
+        line.clear();
+        reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    }
+    // Skip the trailers
+    loop {
+        line.clear();
+        let len = reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        if len == 0 || line.trim().is_empty() {
+            return Ok(());
+        }
+    }
+}
+
+fn http_status_line(code: f64) -> Result<String, String> {
+    if code.fract() != 0.0 || !(100.0..=999.0).contains(&code) {
+        return Err(format!("{code} is not a valid HTTP status code"));
+    }
+    let code = code as u16;
+    let reason = match code {
+        100 => "Continue",
+        101 => "Switching Protocols",
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        304 => "Not Modified",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => return Ok(code.to_string()),
+    };
+    Ok(format!("{code} {reason}"))
+}
+
 impl SysOp {
     pub(crate) fn run(&self, env: &mut Uiua) -> UiuaResult {
         match self {
@@ -1450,6 +2297,66 @@ impl SysOp {
                 let bytes = value_to_gif_bytes(&value, delay).map_err(|e| env.error(e))?;
                 env.backend.show_gif(bytes).map_err(|e| env.error(e))?;
             }
+            SysOp::JsonEncode => {
+                let value = env.pop(1)?;
+                let json = value_to_json(&value).map_err(|e| env.error(e))?;
+                env.push(json.to_string());
+            }
+            SysOp::JsonDecode => {
+                let json: serde_json::Value = match env.pop(1)? {
+                    Value::Char(arr) if arr.rank() <= 1 => {
+                        serde_json::from_str(&arr.data.iter().collect::<String>())
+                    }
+                    Value::Byte(arr) if arr.rank() == 1 => serde_json::from_slice(&arr.data),
+                    Value::Num(arr) if arr.rank() == 1 => {
+                        let bytes = num_bytes(&arr, env)?;
+                        serde_json::from_slice(&bytes)
+                    }
+                    _ => return Err(env.error("JSON must be a string or a list of bytes")),
+                }
+                .map_err(|e| env.error(format!("Failed to decode JSON: {e}")))?;
+                env.push(json_to_value(json));
+            }
+            SysOp::Serialize => {
+                let value = env.pop(1)?;
+                let bytes = value.to_bytes().map_err(|e| env.error(e))?;
+                env.push(Array::<u8>::from(bytes));
+            }
+            SysOp::Deserialize => {
+                let bytes: Vec<u8> = match env.pop(1)? {
+                    Value::Byte(arr) if arr.rank() == 1 => arr.data.into(),
+                    Value::Num(arr) if arr.rank() == 1 => num_bytes(&arr, env)?,
+                    _ => return Err(env.error("Serialized value must be a list of bytes")),
+                };
+                let value = Value::from_bytes(&bytes)
+                    .map_err(|e| env.error(format!("Failed to deserialize value: {e}")))?;
+                env.push(value);
+            }
+            SysOp::CsvDecode | SysOp::CsvDecodeHeader => {
+                let delimiter = csv_delimiter(env.pop(1)?, env)?;
+                let text = env.pop(2)?.as_string(env, "CSV text must be a string")?;
+                let records = parse_csv(&text, delimiter).map_err(|e| env.error(e))?;
+                env.push(if let SysOp::CsvDecodeHeader = self {
+                    csv_columns_to_value(records)
+                } else {
+                    csv_table_to_value(records)
+                });
+            }
+            SysOp::CsvEncode => {
+                let delimiter = csv_delimiter(env.pop(1)?, env)?;
+                let table = env.pop(2)?;
+                let csv = value_to_csv(&table, delimiter).map_err(|e| env.error(e))?;
+                env.push(csv);
+            }
+            SysOp::CsvWrite => {
+                let path = env.pop(1)?.as_string(env, "Path must be a string")?;
+                let delimiter = csv_delimiter(env.pop(2)?, env)?;
+                let table = env.pop(3)?;
+                let csv = value_to_csv(&table, delimiter).map_err(|e| env.error(e))?;
+                env.backend
+                    .file_write_all(&path, csv.as_bytes())
+                    .map_err(|e| env.error(e))?;
+            }
             SysOp::AudioDecode => {
                 let bytes = match env.pop(1)? {
                     Value::Byte(arr) => {
@@ -1534,6 +2441,159 @@ impl SysOp {
                     .max(0.0);
                 env.backend.sleep(seconds).map_err(|e| env.error(e))?;
             }
+            SysOp::UdpBind => {
+                let addr = env.pop(1)?.as_string(env, "Address must be a string")?;
+                let handle = env.backend.udp_bind(&addr).map_err(|e| env.error(e))?;
+                env.push(handle);
+            }
+            SysOp::UdpSendTo => {
+                let message: Vec<u8> = match env.pop(1)? {
+                    Value::Num(arr) => num_bytes(&arr, env)?,
+                    Value::Byte(arr) => arr.data.into(),
+                    Value::Char(arr) => arr.data.iter().collect::<String>().into(),
+                    Value::Func(_) => return Err(env.error("Cannot send function array")),
+                };
+                let addr = env.pop(2)?.as_string(env, "Address must be a string")?;
+                let handle = env
+                    .pop(3)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                env.backend
+                    .udp_send_to(handle, &addr, &message)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::UdpReceiveFrom => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let (message, addr) = env
+                    .backend
+                    .udp_recv_from(handle)
+                    .map_err(|e| env.error(e))?;
+                env.push(addr);
+                env.push(Array::<u8>::from(message));
+            }
+            SysOp::UdpSetReadTimeout => {
+                let timeout = pop_timeout(env)?;
+                let handle = env
+                    .pop(2)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                env.backend
+                    .udp_set_read_timeout(handle, timeout)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::HttpReadRequest => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let head = (env.backend)
+                    .read_until(handle, b"\r\n\r\n")
+                    .map_err(|e| env.error(e))?;
+                let request = parse_http_request(&head).map_err(|e| env.error(e))?;
+                let body = if request.content_length > 0 {
+                    (env.backend)
+                        .read(handle, request.content_length)
+                        .map_err(|e| env.error(e))?
+                } else {
+                    Vec::new()
+                };
+                let parts = [
+                    request.method.into(),
+                    request.path.into(),
+                    headers_to_value(request.headers),
+                    Array::<u8>::from(body).into(),
+                ];
+                let boxed = |value: Value| Arc::new(Function::constant(value));
+                env.push(parts.into_iter().map(boxed).collect::<Value>());
+            }
+            SysOp::HttpWriteResponse => {
+                let status = env.pop(1)?;
+                let headers = env.pop(2)?;
+                let body: Vec<u8> = match env.pop(3)? {
+                    Value::Num(arr) => num_bytes(&arr, env)?,
+                    Value::Byte(arr) => arr.data.into(),
+                    Value::Char(arr) => arr.data.iter().collect::<String>().into(),
+                    Value::Func(_) => return Err(env.error("Cannot write function array")),
+                };
+                let handle = env
+                    .pop(4)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let response = http_response(status, headers, body).map_err(|e| env.error(e))?;
+                (env.backend)
+                    .write(handle, &response)
+                    .map_err(|e| env.error(e))?;
+            }
+            SysOp::HttpRequest => {
+                let url = env.pop(1)?.as_string(env, "URL must be a string")?;
+                let method = env.pop(2)?.as_string(env, "Method must be a string")?;
+                if method.is_empty() || !method.chars().all(|c| c.is_ascii_alphabetic()) {
+                    return Err(env.error(format!("{method:?} is not a valid HTTP method")));
+                }
+                let headers = value_to_headers(env.pop(3)?).map_err(|e| env.error(e))?;
+                let body: Vec<u8> = match env.pop(4)? {
+                    Value::Num(arr) => num_bytes(&arr, env)?,
+                    Value::Byte(arr) => arr.data.into(),
+                    Value::Char(arr) => arr.data.iter().collect::<String>().into(),
+                    Value::Func(_) => return Err(env.error("Cannot send function array")),
+                };
+                let url = parse_http_url(&url).map_err(|e| env.error(e))?;
+                let request = build_http_request(&method, &url, &headers, body);
+                let response = (env.backend)
+                    .http_request(&url.host, url.port, url.tls, &request)
+                    .map_err(|e| env.error(e))?;
+                env.push(Array::<u8>::from(response.body));
+                env.push(headers_to_value(response.headers));
+                env.push(response.status as f64);
+            }
+            SysOp::Ffi => {
+                let file = env
+                    .pop(1)?
+                    .as_string(env, "Library path must be a string")?;
+                let decl: FfiDecl = (env.pop(2)?)
+                    .as_string(env, "Function declaration must be a string")?
+                    .parse()
+                    .map_err(|e: String| env.error(e))?;
+                let args: Vec<Value> = env.pop(3)?.into_rows().collect();
+                let result = env
+                    .backend
+                    .ffi(&file, &decl, &args)
+                    .map_err(|e| env.error(e))?;
+                env.push(result);
+            }
+            SysOp::Channel => {
+                let handle = env.backend.channel().map_err(|e| env.error(e))?;
+                env.push(handle);
+            }
+            SysOp::Send => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let value = env.pop(2)?;
+                env.backend.send(handle, value).map_err(|e| env.error(e))?;
+            }
+            SysOp::Receive => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let value = env.backend.recv(handle).map_err(|e| env.error(e))?;
+                env.push(value);
+            }
+            SysOp::TryReceive => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let value = (env.backend.try_recv(handle))
+                    .and_then(|value| value.ok_or_else(|| "The channel is empty".into()))
+                    .map_err(|e| env.error(e))?;
+                env.push(value);
+            }
             SysOp::TcpListen => {
                 let addr = env.pop(1)?.as_string(env, "Address must be a string")?;
                 let handle = env.backend.tcp_listen(&addr).map_err(|e| env.error(e))?;
@@ -1552,6 +2612,14 @@ impl SysOp {
                 let handle = env.backend.tcp_connect(&addr).map_err(|e| env.error(e))?;
                 env.push(handle);
             }
+            SysOp::UdpAddr => {
+                let handle = env
+                    .pop(1)?
+                    .as_nat(env, "Handle must be an natural number")?
+                    .into();
+                let addr = env.backend.udp_addr(handle).map_err(|e| env.error(e))?;
+                env.push(addr);
+            }
             SysOp::TcpAddr => {
                 let handle = env
                     .pop(1)?
@@ -1570,12 +2638,7 @@ impl SysOp {
                     .map_err(|e| env.error(e))?;
             }
             SysOp::TcpSetReadTimeout => {
-                let timeout = env.pop(1)?.as_num(env, "Timeout must be a number")?.abs();
-                let timeout = if timeout.is_infinite() {
-                    None
-                } else {
-                    Some(Duration::from_secs_f64(timeout))
-                };
+                let timeout = pop_timeout(env)?;
                 let handle = env
                     .pop(2)?
                     .as_nat(env, "Handle must be an natural number")?
@@ -1585,12 +2648,7 @@ impl SysOp {
                     .map_err(|e| env.error(e))?;
             }
             SysOp::TcpSetWriteTimeout => {
-                let timeout = env.pop(1)?.as_num(env, "Timeout must be a number")?.abs();
-                let timeout = if timeout.is_infinite() {
-                    None
-                } else {
-                    Some(Duration::from_secs_f64(timeout))
-                };
+                let timeout = pop_timeout(env)?;
                 let handle = env
                     .pop(2)?
                     .as_nat(env, "Handle must be an natural number")?
@@ -1648,6 +2706,34 @@ impl SysOp {
     }
 }
 
+/// Convert numbers to bytes, failing if any of them is not a byte
+fn num_bytes(arr: &Array<f64>, env: &Uiua) -> UiuaResult<Vec<u8>> {
+    (arr.data.iter())
+        .map(|&x| {
+            if x.fract() == 0.0 && (0.0..=255.0).contains(&x) {
+                Ok(x as u8)
+            } else {
+                Err(env.error(format!("Expected bytes, but the data contains {x}")))
+            }
+        })
+        .collect()
+}
+
+/// Pop a timeout in seconds, where infinity means no timeout
+fn pop_timeout(env: &mut Uiua) -> UiuaResult<Option<Duration>> {
+    let timeout = env.pop(1)?.as_num(env, "Timeout must be a number")?;
+    if timeout.is_nan() {
+        return Err(env.error("Timeout cannot be NaN"));
+    }
+    let timeout = timeout.abs();
+    if timeout.is_infinite() {
+        return Ok(None);
+    }
+    Duration::try_from_secs_f64(timeout)
+        .map(Some)
+        .map_err(|_| env.error(format!("Timeout of {timeout} seconds is too large")))
+}
+
 fn value_to_command(value: &Value, env: &Uiua) -> UiuaResult<(String, Vec<String>)> {
     let mut strings = Vec::new();
     match value {
@@ -1990,3 +3076,455 @@ pub fn value_to_gif_bytes(value: &Value, frame_rate: f64) -> Result<Vec<u8>, Str
     drop(encoder);
     Ok(bytes.into_inner())
 }
+
+/// Convert a value to JSON
+///
+/// JSON cannot represent `NaN` or infinities, so they are encoded as `null`.
+pub fn value_to_json(value: &Value) -> Result<serde_json::Value, String> {
+    use serde_json::Value as Json;
+    fn nested<T>(
+        shape: &[usize],
+        data: &[T],
+        f: &impl Fn(&T) -> Result<Json, String>,
+    ) -> Result<Json, String> {
+        match shape {
+            [] => f(&data[0]),
+            [len, rest @ ..] => {
+                let row_len: usize = rest.iter().product();
+                (0..*len)
+                    .map(|i| nested(rest, &data[i * row_len..(i + 1) * row_len], f))
+                    .collect::<Result<_, _>>()
+                    .map(Json::Array)
+            }
+        }
+    }
+    fn num(n: f64) -> Json {
+        if n.fract() == 0.0 && n.abs() < 2f64.powi(53) {
+            Json::from(n as i64)
+        } else {
+            serde_json::Number::from_f64(n).map_or(Json::Null, Json::Number)
+        }
+    }
+    fn unbox(f: &Arc<Function>) -> Result<&Value, String> {
+        f.as_constant()
+            .ok_or_else(|| format!("Cannot encode function {f} as JSON"))
+    }
+    Ok(match value {
+        Value::Num(arr) => nested(&arr.shape, &arr.data, &|&n| Ok(num(n)))?,
+        Value::Byte(arr) => nested(&arr.shape, &arr.data, &|&b| Ok(Json::from(b)))?,
+        Value::Char(arr) => match arr.rank() {
+            0 => Json::String(arr.data[0].to_string()),
+            1 => Json::String(arr.data.iter().collect()),
+            _ => Json::Array(
+                value
+                    .rows()
+                    .map(|row| value_to_json(&row))
+                    .collect::<Result<_, _>>()?,
+            ),
+        },
+        Value::Func(arr) => {
+            let is_object = arr.rank() == 2
+                && arr.shape[1] == 2
+                && (arr.data.iter().step_by(2))
+                    .all(|f| matches!(f.as_constant(), Some(Value::Char(key)) if key.rank() == 1));
+            if is_object {
+                let mut map = serde_json::Map::with_capacity(arr.row_count());
+                for pair in arr.data.chunks_exact(2) {
+                    let key = unbox(&pair[0])?.as_char_array().unwrap();
+                    let value = value_to_json(unbox(&pair[1])?)?;
+                    map.insert(key.data.iter().collect(), value);
+                }
+                Json::Object(map)
+            } else {
+                nested(&arr.shape, &arr.data, &|f| value_to_json(unbox(f)?))?
+            }
+        }
+    })
+}
+
+/// Convert JSON to a value
+pub fn json_to_value(json: serde_json::Value) -> Value {
+    use serde_json::Value as Json;
+    let boxed = |value: Value| Arc::new(Function::constant(value));
+    match json {
+        Json::Null => f64::NAN.into(),
+        Json::Bool(b) => (b as u8 as f64).into(),
+        Json::Number(n) => n.as_f64().unwrap_or(f64::NAN).into(),
+        Json::String(s) => s.into(),
+        Json::Array(items) => {
+            let values: Vec<Value> = items.into_iter().map(json_to_value).collect();
+            if values.is_empty() {
+                Array::<f64>::new(tiny_vec![0], Vec::new()).into()
+            } else if values.iter().all(|v| matches!(v, Value::Num(_)))
+                && values.windows(2).all(|w| w[0].shape() == w[1].shape())
+            {
+                Value::from_row_values_infallible(values)
+            } else {
+                values.into_iter().map(boxed).collect()
+            }
+        }
+        Json::Object(map) => {
+            let len = map.len();
+            let data: Vec<_> = (map.into_iter())
+                .flat_map(|(key, value)| [boxed(key.into()), boxed(json_to_value(value))])
+                .collect();
+            Array::new(tiny_vec![len, 2], data).into()
+        }
+    }
+}
+
+fn csv_delimiter(value: Value, env: &Uiua) -> UiuaResult<char> {
+    let delimiter = value.as_string(env, "Delimiter must be a character")?;
+    let mut chars = delimiter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c != '"' && c != '\n' && c != '\r' => Ok(c),
+        _ => Err(env.error(format!(
+            "Delimiter must be a single character other than a quote or newline, \
+            but it is {delimiter:?}"
+        ))),
+    }
+}
+
+/// Parse CSV text into records of fields
+fn parse_csv(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    loop {
+        // Parse a field
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            let start_line = line;
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        field.push(c)
+                    }
+                    None => return Err(format!("Unclosed quote on line {start_line}")),
+                }
+            }
+            match chars.peek() {
+                Some(&c) if c == delimiter || c == '\n' || c == '\r' => {}
+                None => {}
+                Some(c) => {
+                    return Err(format!(
+                        "Expected delimiter or newline after closing quote \
+                        on line {line}, but found {c:?}"
+                    ))
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c == delimiter || c == '\n' || c == '\r' {
+                    break;
+                }
+                field.push(c);
+                chars.next();
+            }
+        }
+        record.push(take(&mut field));
+        // End the field
+        match chars.next() {
+            Some(c) if c == delimiter => continue,
+            Some('\r') if chars.peek() == Some(&'\n') => {
+                chars.next();
+            }
+            Some('\r' | '\n') => {}
+            Some(_) => unreachable!(),
+            None if record.len() == 1 && record[0].is_empty() => break,
+            None => {}
+        }
+        // End the record
+        line += 1;
+        if record.len() == 1 && record[0].is_empty() {
+            // Skip empty lines
+            record.clear();
+        } else {
+            if let Some(first) = records.first().map(Vec::len) {
+                if record.len() != first {
+                    return Err(format!(
+                        "Record on line {} has {} fields, but the first record has {first}",
+                        line - 1,
+                        record.len()
+                    ));
+                }
+            }
+            records.push(take(&mut record));
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+    }
+    Ok(records)
+}
+
+/// Parse a column of CSV fields as numbers
+///
+/// Empty fields are `NaN`. Returns `None` if the column is not numeric.
+fn parse_csv_column<'a>(fields: impl IntoIterator<Item = &'a String>) -> Option<Vec<f64>> {
+    let mut any = false;
+    let nums = fields
+        .into_iter()
+        .map(|field| {
+            let field = field.trim();
+            if field.is_empty() {
+                Some(f64::NAN)
+            } else if field.contains(|c: char| c.is_ascii_digit()) {
+                any = true;
+                field.replace('¯', "-").parse().ok()
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<f64>>>()?;
+    any.then_some(nums)
+}
+
+fn csv_table_to_value(records: Vec<Vec<String>>) -> Value {
+    let row_count = records.len();
+    let col_count = records.first().map_or(0, Vec::len);
+    let columns: Vec<Option<Vec<f64>>> = (0..col_count)
+        .map(|i| parse_csv_column(records.iter().map(|record| &record[i])))
+        .collect();
+    let shape = tiny_vec![row_count, col_count];
+    if columns.iter().all(Option::is_some) {
+        let mut data = vec![0.0; row_count * col_count];
+        for (j, column) in columns.into_iter().enumerate() {
+            for (i, n) in column.unwrap().into_iter().enumerate() {
+                data[i * col_count + j] = n;
+            }
+        }
+        Array::new(shape, data).into()
+    } else {
+        let mut data = Vec::with_capacity(row_count * col_count);
+        for (i, record) in records.into_iter().enumerate() {
+            for (j, field) in record.into_iter().enumerate() {
+                let value: Value = match &columns[j] {
+                    Some(nums) => nums[i].into(),
+                    None => field.into(),
+                };
+                data.push(Arc::new(Function::constant(value)));
+            }
+        }
+        Array::new(shape, data).into()
+    }
+}
+
+fn csv_columns_to_value(mut records: Vec<Vec<String>>) -> Value {
+    if records.is_empty() {
+        return Array::<Arc<Function>>::new(tiny_vec![0, 2], Vec::new()).into();
+    }
+    let header = records.remove(0);
+    let mut data = Vec::with_capacity(header.len() * 2);
+    for (i, name) in header.into_iter().enumerate() {
+        let column: Value = match parse_csv_column(records.iter().map(|record| &record[i])) {
+            Some(nums) => nums.into(),
+            None => (records.iter_mut())
+                .map(|record| Arc::new(Function::constant(take(&mut record[i]))))
+                .collect(),
+        };
+        data.push(Arc::new(Function::constant(name)));
+        data.push(Arc::new(Function::constant(column)));
+    }
+    Array::new(tiny_vec![data.len() / 2, 2], data).into()
+}
+
+/// Encode a table as CSV
+pub fn value_to_csv(value: &Value, delimiter: char) -> Result<String, String> {
+    fn field(value: &Value, delimiter: char) -> Result<String, String> {
+        let s = match value {
+            // Empty fields decode as `NaN`, so `NaN` encodes as an empty field
+            Value::Num(arr) if arr.rank() == 0 && arr.data[0].is_nan() => String::new(),
+            Value::Num(arr) if arr.rank() == 0 => arr.data[0].to_string(),
+            Value::Byte(arr) if arr.rank() == 0 => arr.data[0].to_string(),
+            Value::Char(arr) if arr.rank() <= 1 => arr.data.iter().collect(),
+            Value::Func(arr) if arr.rank() == 0 => match arr.as_constant() {
+                Some(value) => return field(value, delimiter),
+                None => return Err("Cannot encode a function as a CSV field".into()),
+            },
+            value => {
+                return Err(format!(
+                    "CSV fields must be scalars or strings, but a field has shape {}",
+                    value.format_shape()
+                ))
+            }
+        };
+        Ok(if s.contains([delimiter, '"', '\n', '\r']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s
+        })
+    }
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    match value {
+        // A table with a header
+        Value::Func(arr)
+            if arr.rank() == 2
+                && arr.shape[1] == 2
+                && arr.data.chunks_exact(2).all(|pair| {
+                    matches!(pair[0].as_constant(), Some(Value::Char(name)) if name.rank() == 1)
+                        && matches!(pair[1].as_constant(), Some(col) if col.rank() == 1 && !matches!(col, Value::Char(_)))
+                }) =>
+        {
+            let columns: Vec<&Value> = (arr.data.chunks_exact(2))
+                .map(|pair| pair[1].as_constant().unwrap())
+                .collect();
+            let row_count = columns.first().map_or(0, |col| col.row_count());
+            if columns.iter().any(|col| col.row_count() != row_count) {
+                return Err("All columns in a CSV table must have the same length".into());
+            }
+            let header = (arr.data.chunks_exact(2))
+                .map(|pair| field(pair[0].as_constant().unwrap(), delimiter))
+                .collect::<Result<_, _>>()?;
+            rows.push(header);
+            let mut columns: Vec<_> = columns.into_iter().map(Value::rows).collect();
+            for _ in 0..row_count {
+                let row = (columns.iter_mut())
+                    .map(|col| field(&col.next().unwrap(), delimiter))
+                    .collect::<Result<_, _>>()?;
+                rows.push(row);
+            }
+        }
+        Value::Char(_) => return Err("Cannot encode a character array as a CSV table".into()),
+        value => match value.rank() {
+            1 => rows.push(
+                (value.rows())
+                    .map(|cell| field(&cell, delimiter))
+                    .collect::<Result<_, _>>()?,
+            ),
+            2 => {
+                for row in value.rows() {
+                    rows.push(
+                        (row.rows())
+                            .map(|cell| field(&cell, delimiter))
+                            .collect::<Result<_, _>>()?,
+                    );
+                }
+            }
+            rank => {
+                return Err(format!(
+                    "CSV tables must be rank 1 or 2, but the table is rank {rank}"
+                ))
+            }
+        },
+    }
+    let mut csv = String::new();
+    for row in rows {
+        csv.push_str(&row.join(&delimiter.to_string()));
+        csv.push('\n');
+    }
+    Ok(csv)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::mpsc::channel, thread};
+
+    use super::*;
+
+    #[test]
+    fn http_server() {
+        let (addr_send, addr_recv) = channel::<String>();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr_recv.recv().unwrap()).unwrap();
+            stream
+                .write_all(
+                    b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello",
+                )
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        let mut env = Uiua::with_native_sys();
+        env.load_str("Listener ← &tcpl \"127.0.0.1:0\"\n&tcpaddr Listener")
+            .unwrap();
+        let addr = env.pop("address").unwrap();
+        addr_send.send(addr.as_string(&env, "").unwrap()).unwrap();
+        env.load_str(
+            r#"
+Socket ← &tcpa Listener
+Request ← &httpr Socket
+⍤"Bad request head" ≅ {"POST" "/echo" [{"Host" "localhost"} {"Content-Length" "5"}]} ↙3 Request
+&httpw 201 [{"Content-Type" "text/plain"}] ⊂ ⊔⊡1 Request ⊂ @  +@\0 ⊔⊡3 Request Socket
+&cl Socket
+&cl Listener
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            client.join().unwrap(),
+            "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nContent-Length: 11\r\n\r\n/echo hello"
+        );
+    }
+
+    #[test]
+    fn closed_channels_are_removed() {
+        let empty = NativeSys.channel().unwrap();
+        NativeSys.close(empty).unwrap();
+        assert!(!NATIVE_SYS.channels.contains_key(&empty));
+        let full = NativeSys.channel().unwrap();
+        NativeSys.send(full, 1.0.into()).unwrap();
+        NativeSys.close(full).unwrap();
+        assert!(NATIVE_SYS.channels.contains_key(&full));
+        assert_eq!(NativeSys.recv(full), Ok(1.0.into()));
+        assert!(!NATIVE_SYS.channels.contains_key(&full));
+    }
+
+    #[test]
+    fn socket_timeouts() {
+        let mut env = Uiua::with_native_sys();
+        env.load_str("Socket ← &udpb \"127.0.0.1:0\"").unwrap();
+        env.load_str("&udpsrt 0.5 Socket").unwrap();
+        env.load_str("&udpsrt ∞ Socket").unwrap();
+        for code in ["&udpsrt NaN Socket", "&udpsrt 1e300 Socket"] {
+            assert!(env.load_str(code).is_err(), "{code} should fail");
+        }
+        env.load_str("&cl Socket").unwrap();
+    }
+
+    #[test]
+    fn http_url() {
+        let url = parse_http_url("http://example.com:8080/a b/ü?q=1 2#frag").unwrap();
+        assert!(!url.tls);
+        assert_eq!((url.host.as_str(), url.port), ("example.com", 8080));
+        assert_eq!(url.path, "/a%20b/%C3%BC?q=1%202");
+        let request = build_http_request("GET", &url, &[], Vec::new());
+        assert!(request
+            .starts_with(b"GET /a%20b/%C3%BC?q=1%202 HTTP/1.1\r\nHost: example.com:8080\r\n"));
+        let url = parse_http_url("https://example.com/a\r\nX-Injected: 1").unwrap();
+        assert_eq!(url.path, "/a%0D%0AX-Injected:%201");
+        assert!(parse_http_url("http://exa mple.com/").is_err());
+        assert!(parse_http_url("http://example.com\r\nX-Injected: 1").is_err());
+    }
+
+    #[test]
+    fn http_header_validation() {
+        let response = |status: Value, name: &str, value: &str| {
+            let header = [name, value].map(|s| Arc::new(Function::constant(s.to_string())));
+            let headers = Array::new(tiny_vec![1, 2], header.to_vec()).into();
+            http_response(status, headers, Vec::new())
+        };
+        assert!(response(200.into(), "X-Ok", "fine").is_ok());
+        assert!(response(200.into(), "X-Split", "a\r\nSet-Cookie: b").is_err());
+        assert!(response(200.into(), "X-Split\r\nSet-Cookie", "b").is_err());
+        assert!(response(200.into(), "Bad Name", "a").is_err());
+        assert!(response(200.into(), "", "a").is_err());
+        assert!(response(
+            String::from("200 OK\r\nSet-Cookie: b").into(),
+            "X-Ok",
+            "fine"
+        )
+        .is_err());
+    }
+}
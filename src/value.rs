@@ -79,6 +79,15 @@ impl Value {
             _ => Err(self),
         }
     }
+    /// Check if calling any function in the value can only affect the stack
+    ///
+    /// Non-function values are always pure.
+    pub(crate) fn is_pure(&self) -> bool {
+        match self {
+            Self::Func(array) => array.data.iter().all(|f| f.is_pure()),
+            _ => true,
+        }
+    }
     pub fn as_function(&self) -> Option<&Arc<Function>> {
         self.as_func_array().and_then(Array::as_scalar)
     }
@@ -941,3 +950,130 @@ impl fmt::Display for Value {
         }
     }
 }
+
+/// The bytes at the start of every value encoded with [`Value::to_bytes`]
+const BYTES_MAGIC: &[u8] = b"UIUA";
+/// The version of the format of [`Value::to_bytes`]
+const BYTES_VERSION: u8 = 1;
+
+impl Value {
+    /// Encode the value in a compact binary format
+    ///
+    /// The encoding keeps the shape and the element type of the value.
+    /// Boxed values are encoded recursively, but functions cannot be encoded.
+    ///
+    /// Values can be decoded with [`Value::from_bytes`].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = BYTES_MAGIC.to_vec();
+        bytes.push(BYTES_VERSION);
+        self.encode_bytes(&mut bytes)?;
+        Ok(bytes)
+    }
+    fn encode_bytes(&self, bytes: &mut Vec<u8>) -> Result<(), String> {
+        let rank = u8::try_from(self.rank())
+            .map_err(|_| format!("Cannot encode an array of rank {}", self.rank()))?;
+        bytes.push(match self {
+            Value::Num(_) => 0,
+            Value::Byte(_) => 1,
+            Value::Char(_) => 2,
+            Value::Func(_) => 3,
+        });
+        bytes.push(rank);
+        for &dim in self.shape() {
+            bytes.extend((dim as u64).to_le_bytes());
+        }
+        match self {
+            Value::Num(arr) => {
+                for n in &arr.data {
+                    bytes.extend(n.to_le_bytes());
+                }
+            }
+            Value::Byte(arr) => bytes.extend_from_slice(&arr.data),
+            Value::Char(arr) => {
+                for &c in &arr.data {
+                    bytes.extend((c as u32).to_le_bytes());
+                }
+            }
+            Value::Func(arr) => {
+                for f in &arr.data {
+                    let value = f.as_constant().ok_or("Cannot encode functions")?;
+                    value.encode_bytes(bytes)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Decode a value encoded with [`Value::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let bytes =
+            (bytes.strip_prefix(BYTES_MAGIC)).ok_or("Bytes do not contain an encoded value")?;
+        let (&version, mut bytes) = bytes.split_first().ok_or("Encoded value is missing")?;
+        if version != BYTES_VERSION {
+            return Err(format!(
+                "Encoded value has version {version}, \
+                but only version {BYTES_VERSION} is supported"
+            ));
+        }
+        let value = Self::decode_bytes(&mut bytes, 0)?;
+        if !bytes.is_empty() {
+            return Err(format!(
+                "Encoded value has {} extra bytes at the end",
+                bytes.len()
+            ));
+        }
+        Ok(value)
+    }
+    fn decode_bytes(bytes: &mut &[u8], depth: usize) -> Result<Self, String> {
+        fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], String> {
+            if bytes.len() < n {
+                return Err("Encoded value ends unexpectedly".into());
+            }
+            let (taken, rest) = bytes.split_at(n);
+            *bytes = rest;
+            Ok(taken)
+        }
+        let header = take(bytes, 2)?;
+        let (tag, rank) = (header[0], header[1] as usize);
+        let elem_size = match tag {
+            0 => 8,
+            1 => 1,
+            2 => 4,
+            // Every boxed value is at least 2 bytes long
+            3 if depth < 1000 => 2,
+            3 => return Err("Encoded value has too many nested boxes".into()),
+            _ => return Err(format!("Encoded value has invalid type {tag}")),
+        };
+        let mut shape = Shape::with_capacity(rank);
+        for chunk in take(bytes, rank * 8)?.chunks_exact(8) {
+            let dim = u64::from_le_bytes(chunk.try_into().unwrap());
+            shape.push(usize::try_from(dim).map_err(|_| "Encoded value is too large")?);
+        }
+        let len = (shape.iter())
+            .try_fold(1usize, |acc, &dim| acc.checked_mul(dim))
+            .filter(|len| len.saturating_mul(elem_size) <= bytes.len())
+            .ok_or("Encoded value ends unexpectedly")?;
+        Ok(match tag {
+            0 => {
+                let data = take(bytes, len * 8)?.chunks_exact(8);
+                let data = data.map(|n| f64::from_le_bytes(n.try_into().unwrap()));
+                Array::new(shape, data.collect::<Vec<_>>()).into()
+            }
+            1 => Array::new(shape, take(bytes, len)?).into(),
+            2 => {
+                let data = take(bytes, len * 4)?.chunks_exact(4).map(|c| {
+                    let c = u32::from_le_bytes(c.try_into().unwrap());
+                    char::from_u32(c).ok_or_else(|| format!("Encoded value has invalid char {c}"))
+                });
+                Array::new(shape, data.collect::<Result<Vec<_>, _>>()?).into()
+            }
+            _ => {
+                let mut data = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let value = Self::decode_bytes(bytes, depth + 1)?;
+                    data.push(Arc::new(Function::constant(value)));
+                }
+                Array::new(shape, data).into()
+            }
+        })
+    }
+}